@@ -1,4 +1,10 @@
 use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use crate::identifiers::MAX_NEIGHBOURHOOD_DEPTH;
+
+/// Radius of the identifier mesh at scale 1.
+pub const IDENTIFIER_RADIUS: f32 = 0.1;
 
 #[derive(Reflect, Resource, Default)]
 #[reflect(Resource)]
@@ -7,8 +13,43 @@ pub struct MyAssets {
     pub identifier_material_handle: Handle<StandardMaterial>,
     pub identifier_selected_material_handle: Handle<StandardMaterial>,
     pub identifier_connected_material_handle: Handle<StandardMaterial>,
+    /// Identifiers `i + 1` connections away from the selection, the first one
+    /// being `identifier_connected_material_handle`.
+    pub identifier_hop_material_handles: Vec<Handle<StandardMaterial>>,
+    pub identifier_path_material_handle: Handle<StandardMaterial>,
+    pub identifier_dimmed_material_handle: Handle<StandardMaterial>,
     pub connection_mesh_handle: Handle<Mesh>,
     pub connection_material_handle: Handle<StandardMaterial>,
+    pub connection_path_material_handle: Handle<StandardMaterial>,
+    pub connection_dimmed_material_handle: Handle<StandardMaterial>,
+}
+
+/// Identifier materials for colours other than the default one, shared by
+/// every identifier of the same colour.
+#[derive(Resource, Default)]
+pub struct ColorMaterials(HashMap<[u8; 4], Handle<StandardMaterial>>);
+
+impl ColorMaterials {
+    pub fn get_or_add(
+        &mut self,
+        color: Color,
+        materials: &mut Assets<StandardMaterial>,
+    ) -> Handle<StandardMaterial> {
+        self.0
+            .entry(color.as_rgba_u8())
+            .or_insert_with(|| {
+                materials.add(StandardMaterial {
+                    base_color: color,
+                    alpha_mode: if color.a() < 1.0 {
+                        AlphaMode::Blend
+                    } else {
+                        AlphaMode::Opaque
+                    },
+                    ..Default::default()
+                })
+            })
+            .clone()
+    }
 }
 
 pub struct AssetsPlugin;
@@ -16,6 +57,7 @@ pub struct AssetsPlugin;
 impl Plugin for AssetsPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<MyAssets>()
+            .init_resource::<ColorMaterials>()
             .register_type::<MyAssets>()
             .add_systems(Startup, setup);
     }
@@ -28,7 +70,7 @@ fn setup(
 ) {
     my_assets.identifier_mesh_handle = meshes.add(
         Mesh::try_from(shape::Icosphere {
-            radius: 0.1,
+            radius: IDENTIFIER_RADIUS,
             subdivisions: 2,
         })
         .unwrap(),
@@ -37,6 +79,16 @@ fn setup(
         base_color: Color::rgb(0.1, 0.1, 0.9),
         ..Default::default()
     });
+    // Further hops turn from blue towards green.
+    my_assets.identifier_hop_material_handles =
+        std::iter::once(my_assets.identifier_connected_material_handle.clone())
+            .chain((1..MAX_NEIGHBOURHOOD_DEPTH).map(|hop| {
+                color_materials.add(StandardMaterial {
+                    base_color: Color::hsl(240.0 - 30.0 * hop as f32, 0.8, 0.5),
+                    ..Default::default()
+                })
+            }))
+            .collect();
     my_assets.identifier_selected_material_handle = color_materials.add(StandardMaterial {
         base_color: Color::RED,
         ..Default::default()
@@ -46,6 +98,15 @@ fn setup(
         alpha_mode: AlphaMode::Blend,
         ..Default::default()
     });
+    my_assets.identifier_path_material_handle = color_materials.add(StandardMaterial {
+        base_color: Color::ORANGE_RED,
+        ..Default::default()
+    });
+    my_assets.identifier_dimmed_material_handle = color_materials.add(StandardMaterial {
+        base_color: Color::GRAY.with_a(0.15),
+        alpha_mode: AlphaMode::Blend,
+        ..Default::default()
+    });
 
     my_assets.connection_mesh_handle = meshes.add(Mesh::from(shape::Cylinder {
         radius: 0.02,
@@ -58,4 +119,13 @@ fn setup(
         alpha_mode: AlphaMode::Blend,
         ..Default::default()
     });
+    my_assets.connection_path_material_handle = color_materials.add(StandardMaterial {
+        base_color: Color::ORANGE_RED,
+        ..Default::default()
+    });
+    my_assets.connection_dimmed_material_handle = color_materials.add(StandardMaterial {
+        base_color: Color::GRAY.with_a(0.1),
+        alpha_mode: AlphaMode::Blend,
+        ..Default::default()
+    });
 }
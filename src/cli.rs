@@ -0,0 +1,38 @@
+use bevy::prelude::*;
+use std::path::PathBuf;
+
+/// Command line arguments: `graph-view [--seed N] [--session SESSION] [PATH]`.
+#[derive(Resource, Default, Debug)]
+pub struct CliArgs {
+    /// Graph file to import at startup.
+    pub path: Option<PathBuf>,
+    /// Seed for `SeededRng`. A random one is picked when missing.
+    pub seed: Option<u64>,
+    /// Session file to open at startup.
+    pub session: Option<PathBuf>,
+}
+
+impl CliArgs {
+    pub fn from_env() -> Self {
+        let mut args = CliArgs::default();
+        let mut env_args = std::env::args().skip(1);
+        while let Some(arg) = env_args.next() {
+            if arg == "--seed" {
+                match env_args.next().map(|seed| seed.parse::<u64>()) {
+                    Some(Ok(seed)) => args.seed = Some(seed),
+                    _ => eprintln!("--seed expects a number"),
+                }
+            } else if arg == "--session" {
+                match env_args.next() {
+                    Some(path) => args.session = Some(PathBuf::from(path)),
+                    None => eprintln!("--session expects a path"),
+                }
+            } else if args.path.is_none() {
+                args.path = Some(PathBuf::from(arg));
+            } else {
+                eprintln!("Ignoring extra argument {}", arg);
+            }
+        }
+        args
+    }
+}
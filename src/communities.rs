@@ -0,0 +1,452 @@
+use bevy::prelude::*;
+use bevy_egui::EguiContext;
+use bevy_inspector_egui::egui;
+use bevy_window::PrimaryWindow;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::collections::BTreeMap;
+
+use crate::assets::{ColorMaterials, MyAssets};
+use crate::events::{ClearCommunitiesEvent, DetectCommunitiesEvent};
+use crate::graph_index::GraphIndex;
+use crate::identifiers::{Identifier, IdentifierMaterial};
+use crate::jobs::{poll, Job, Jobs, Progress};
+use crate::resources::SeededRng;
+
+/// Label propagation, and each level of Louvain, stops after this many sweeps
+/// over the identifiers even if they still move.
+const MAX_SWEEPS: usize = 100;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum CommunityAlgorithm {
+    #[default]
+    Louvain,
+    LabelPropagation,
+}
+
+impl CommunityAlgorithm {
+    pub const ALL: [CommunityAlgorithm; 2] = [
+        CommunityAlgorithm::Louvain,
+        CommunityAlgorithm::LabelPropagation,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CommunityAlgorithm::Louvain => "Louvain",
+            CommunityAlgorithm::LabelPropagation => "Label propagation",
+        }
+    }
+}
+
+/// Community an identifier was put in by the last detection. Communities are
+/// numbered from the largest down.
+#[derive(Component, Reflect, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[reflect(Component)]
+pub struct Community(pub usize);
+
+/// Outcome of the last detection, for the panel.
+#[derive(Resource, Default)]
+pub struct Communities {
+    pub algorithm: CommunityAlgorithm,
+    pub modularity: f64,
+    /// Size and colour of each community, largest first.
+    pub communities: Vec<(usize, Color)>,
+}
+
+pub struct CommunitiesPlugin;
+
+impl Plugin for CommunitiesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Communities>()
+            .register_type::<Community>()
+            .add_systems(Update, detect_communities)
+            .add_systems(Update, clear_communities);
+    }
+}
+
+pub fn communities_ui(
+    query: Query<&mut EguiContext, With<PrimaryWindow>>,
+    mut algorithm: Local<CommunityAlgorithm>,
+    communities: Res<Communities>,
+    mut ev: EventWriter<DetectCommunitiesEvent>,
+    mut ev_clear: EventWriter<ClearCommunitiesEvent>,
+) {
+    let mut egui_context = query.single().clone();
+
+    egui::Window::new("Communities")
+        .vscroll(false)
+        .hscroll(false)
+        .default_width(250.0)
+        .resizable(false)
+        .show(egui_context.get_mut(), |ui| {
+            egui::ComboBox::from_label("Algorithm")
+                .selected_text(algorithm.name())
+                .show_ui(ui, |ui| {
+                    for option in CommunityAlgorithm::ALL {
+                        ui.selectable_value(&mut *algorithm, option, option.name());
+                    }
+                });
+            ui.horizontal(|ui| {
+                if ui.button("Detect").clicked() {
+                    ev.send(DetectCommunitiesEvent {
+                        algorithm: *algorithm,
+                    });
+                }
+                if ui.button("Clear").clicked() {
+                    ev_clear.send(ClearCommunitiesEvent);
+                }
+            });
+
+            if communities.communities.is_empty() {
+                return;
+            }
+            ui.separator();
+            ui.label(format!(
+                "{}: {} communities, modularity {:.3}",
+                communities.algorithm.name(),
+                communities.communities.len(),
+                communities.modularity
+            ));
+            egui::ScrollArea::vertical()
+                .max_height(200.0)
+                .show(ui, |ui| {
+                    for (i, (size, color)) in communities.communities.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            let [r, g, b, _] = color.as_rgba_u8();
+                            ui.colored_label(egui::Color32::from_rgb(r, g, b), "⏺");
+                            ui.label(format!("{}: {} identifiers", i, size));
+                        });
+                    }
+                });
+        });
+}
+
+fn detect_communities(
+    mut commands: Commands,
+    mut ev: EventReader<DetectCommunitiesEvent>,
+    mut job: Local<Option<Job<(CommunityAlgorithm, Vec<Entity>, Vec<usize>, f64)>>>,
+    mut jobs: ResMut<Jobs>,
+    mut seeded_rng: ResMut<SeededRng>,
+    mut communities: ResMut<Communities>,
+    mut color_materials: ResMut<ColorMaterials>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    graph_index: Res<GraphIndex>,
+) {
+    for e in ev.read() {
+        let n = graph_index.len();
+        if n == 0 {
+            continue;
+        }
+        let edges = graph_index.edges();
+        let entities: Vec<Entity> = (0..n).map(|i| graph_index.entity(i)).collect();
+        let algorithm = e.algorithm;
+        let mut rng = StdRng::seed_from_u64(seeded_rng.rng().gen());
+        *job = Some(jobs.spawn(algorithm.name(), move |progress| {
+            let labels = match algorithm {
+                CommunityAlgorithm::Louvain => louvain(n, &edges, progress),
+                CommunityAlgorithm::LabelPropagation => {
+                    label_propagation(&mut rng, n, &edges, progress)
+                }
+            };
+            let labels = by_size(labels);
+            let modularity = modularity(n, &edges, &labels);
+            (algorithm, entities, labels, modularity)
+        }));
+    }
+
+    let Some((algorithm, entities, labels, modularity)) = poll(&mut job) else {
+        return;
+    };
+    let count = labels.iter().max().map_or(0, |max| max + 1);
+    let mut sizes = vec![0; count];
+    labels.iter().for_each(|&label| sizes[label] += 1);
+    let palette: Vec<(Color, Handle<StandardMaterial>)> = (0..count)
+        .map(|i| {
+            let color = community_color(i);
+            (color, color_materials.get_or_add(color, &mut materials))
+        })
+        .collect();
+
+    for (entity, label) in entities.into_iter().zip(labels) {
+        if let Some(mut entity) = commands.get_entity(entity) {
+            let material = palette[label].1.clone();
+            entity.insert((
+                Community(label),
+                material.clone(),
+                IdentifierMaterial(material),
+            ));
+        }
+    }
+    *communities = Communities {
+        algorithm,
+        modularity,
+        communities: sizes
+            .into_iter()
+            .zip(palette.into_iter().map(|(color, _)| color))
+            .collect(),
+    };
+}
+
+fn clear_communities(
+    mut commands: Commands,
+    mut ev: EventReader<ClearCommunitiesEvent>,
+    mut communities: ResMut<Communities>,
+    my_assets: Res<MyAssets>,
+    identifier_query: Query<Entity, (With<Identifier>, With<Community>)>,
+) {
+    for _ in ev.read() {
+        for entity in identifier_query.iter() {
+            commands
+                .entity(entity)
+                .remove::<(Community, IdentifierMaterial)>()
+                .insert(my_assets.identifier_material_handle.clone());
+        }
+        *communities = Communities::default();
+    }
+}
+
+/// Hues a golden angle apart, so neighbouring community numbers look different
+/// however many communities there are.
+fn community_color(community: usize) -> Color {
+    Color::hsl((community as f32 * 137.508) % 360.0, 0.7, 0.55)
+}
+
+/// Renumbers labels `0..k` from the largest community down.
+fn by_size(labels: Vec<usize>) -> Vec<usize> {
+    let mut sizes: BTreeMap<usize, usize> = BTreeMap::new();
+    labels
+        .iter()
+        .for_each(|&label| *sizes.entry(label).or_default() += 1);
+    let mut order: Vec<(usize, usize)> = sizes.into_iter().collect();
+    order.sort_by_key(|&(label, size)| (std::cmp::Reverse(size), label));
+    let renumber: BTreeMap<usize, usize> = order
+        .into_iter()
+        .enumerate()
+        .map(|(new, (label, _))| (label, new))
+        .collect();
+    labels.into_iter().map(|label| renumber[&label]).collect()
+}
+
+/// Symmetric weighted adjacency. A self-loop counts twice towards its
+/// identifier's degree, as both of its ends are there.
+fn weighted_adjacency(n: usize, edges: &[(usize, usize)]) -> Vec<Vec<(usize, f64)>> {
+    let mut adjacency: Vec<BTreeMap<usize, f64>> = vec![BTreeMap::new(); n];
+    for &(i, j) in edges {
+        if i == j {
+            *adjacency[i].entry(i).or_default() += 2.0;
+        } else {
+            *adjacency[i].entry(j).or_default() += 1.0;
+            *adjacency[j].entry(i).or_default() += 1.0;
+        }
+    }
+    adjacency
+        .into_iter()
+        .map(|neighbours| neighbours.into_iter().collect())
+        .collect()
+}
+
+/// Newman's modularity of `labels`, ignoring connection direction.
+fn modularity(n: usize, edges: &[(usize, usize)], labels: &[usize]) -> f64 {
+    let adjacency = weighted_adjacency(n, edges);
+    let total: f64 = adjacency.iter().flatten().map(|(_, w)| w).sum();
+    if total == 0.0 {
+        return 0.0;
+    }
+    let count = labels.iter().max().map_or(0, |max| max + 1);
+    let mut inside = vec![0.0; count];
+    let mut degrees = vec![0.0; count];
+    for (i, neighbours) in adjacency.iter().enumerate() {
+        for &(j, w) in neighbours {
+            degrees[labels[i]] += w;
+            if labels[i] == labels[j] {
+                inside[labels[i]] += w;
+            }
+        }
+    }
+    inside
+        .iter()
+        .zip(degrees.iter())
+        .map(|(inside, degree)| inside / total - (degree / total).powi(2))
+        .sum()
+}
+
+/// Blondel et al., "Fast unfolding of communities in large networks" (2008).
+/// Identifiers move to the neighbouring community that raises modularity the
+/// most until none does, then each community becomes a node of a smaller graph
+/// and the same is done again, until nothing moves.
+fn louvain(n: usize, edges: &[(usize, usize)], progress: &Progress) -> Vec<usize> {
+    let mut membership: Vec<usize> = (0..n).collect();
+    let mut adjacency = weighted_adjacency(n, edges);
+    let total: f64 = adjacency.iter().flatten().map(|(_, w)| w).sum();
+    if total == 0.0 {
+        return membership;
+    }
+
+    loop {
+        if progress.is_cancelled() {
+            break;
+        }
+        let nodes = adjacency.len();
+        let degree: Vec<f64> = adjacency
+            .iter()
+            .map(|neighbours| neighbours.iter().map(|(_, w)| w).sum())
+            .collect();
+        let mut community: Vec<usize> = (0..nodes).collect();
+        let mut community_degree = degree.clone();
+        let mut links = vec![0.0; nodes];
+        let mut touched: Vec<usize> = vec![];
+
+        let mut improved = false;
+        for _ in 0..MAX_SWEEPS {
+            if progress.is_cancelled() {
+                break;
+            }
+            let mut moved = false;
+            for node in 0..nodes {
+                let own = community[node];
+                community_degree[own] -= degree[node];
+                for &(neighbour, w) in adjacency[node].iter() {
+                    if neighbour != node {
+                        let c = community[neighbour];
+                        if links[c] == 0.0 {
+                            touched.push(c);
+                        }
+                        links[c] += w;
+                    }
+                }
+
+                // Gain of joining `c`, up to a factor shared by every choice.
+                let gain = |c: usize| links[c] - community_degree[c] * degree[node] / total;
+                let mut best = own;
+                let mut best_gain = gain(own);
+                for &c in touched.iter() {
+                    if gain(c) > best_gain {
+                        best = c;
+                        best_gain = gain(c);
+                    }
+                }
+                community_degree[best] += degree[node];
+                community[node] = best;
+                if best != own {
+                    moved = true;
+                    improved = true;
+                }
+                touched.drain(..).for_each(|c| links[c] = 0.0);
+            }
+            if !moved {
+                break;
+            }
+        }
+        if !improved {
+            break;
+        }
+
+        // Fold each community into one node of the next level.
+        let mut renumber = vec![usize::MAX; nodes];
+        let mut count = 0;
+        for &c in community.iter() {
+            if renumber[c] == usize::MAX {
+                renumber[c] = count;
+                count += 1;
+            }
+        }
+        membership
+            .iter_mut()
+            .for_each(|m| *m = renumber[community[*m]]);
+        let mut folded: Vec<BTreeMap<usize, f64>> = vec![BTreeMap::new(); count];
+        for (node, neighbours) in adjacency.iter().enumerate() {
+            for &(neighbour, w) in neighbours {
+                *folded[renumber[community[node]]]
+                    .entry(renumber[community[neighbour]])
+                    .or_default() += w;
+            }
+        }
+        adjacency = folded
+            .into_iter()
+            .map(|neighbours| neighbours.into_iter().collect())
+            .collect();
+        progress.set(1.0 - count as f32 / n as f32);
+    }
+    membership
+}
+
+/// Raghavan, Albert and Kumara (2007): every identifier repeatedly takes the
+/// label most of its neighbours have, visiting them in random order and
+/// breaking ties at random, until no label changes.
+fn label_propagation(
+    rng: &mut impl Rng,
+    n: usize,
+    edges: &[(usize, usize)],
+    progress: &Progress,
+) -> Vec<usize> {
+    let adjacency = weighted_adjacency(n, edges);
+    let mut labels: Vec<usize> = (0..n).collect();
+    let mut order: Vec<usize> = (0..n).collect();
+    let mut counts: BTreeMap<usize, f64> = BTreeMap::new();
+
+    for sweep in 0..MAX_SWEEPS {
+        if progress.is_cancelled() {
+            break;
+        }
+        progress.set(sweep as f32 / MAX_SWEEPS as f32);
+        order.shuffle(rng);
+        let mut changed = false;
+        for &node in order.iter() {
+            counts.clear();
+            for &(neighbour, w) in adjacency[node].iter() {
+                if neighbour != node {
+                    *counts.entry(labels[neighbour]).or_default() += w;
+                }
+            }
+            let Some(most) = counts.values().copied().reduce(f64::max) else {
+                continue;
+            };
+            let candidates: Vec<usize> = counts
+                .iter()
+                .filter(|(_, &count)| count == most)
+                .map(|(&label, _)| label)
+                .collect();
+            // Keeping the current label when it is among the most common lets sweeps settle.
+            if candidates.contains(&labels[node]) {
+                continue;
+            }
+            if let Some(&label) = candidates.choose(rng) {
+                labels[node] = label;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    labels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Triangles 0-1-2 and 3-4-5 joined by the bridge 2-3.
+    const TWO_TRIANGLES: [(usize, usize); 7] =
+        [(0, 1), (1, 2), (2, 0), (3, 4), (4, 5), (5, 3), (2, 3)];
+
+    #[test]
+    fn modularity_of_two_triangles() {
+        // 2 * (3/7 - (7/14)^2)
+        let q = modularity(6, &TWO_TRIANGLES, &[0, 0, 0, 1, 1, 1]);
+        assert!((q - 5.0 / 14.0).abs() < 1e-9, "{}", q);
+        assert_eq!(modularity(6, &TWO_TRIANGLES, &[0; 6]), 0.0);
+        assert_eq!(modularity(3, &[], &[0, 1, 2]), 0.0);
+    }
+
+    #[test]
+    fn louvain_splits_at_the_bridge() {
+        let labels = louvain(6, &TWO_TRIANGLES, &Progress::new("test"));
+        assert_eq!(labels[0], labels[1]);
+        assert_eq!(labels[0], labels[2]);
+        assert_eq!(labels[3], labels[4]);
+        assert_eq!(labels[3], labels[5]);
+        assert_ne!(labels[0], labels[3]);
+    }
+}
@@ -0,0 +1,492 @@
+use bevy::prelude::*;
+use bevy_egui::EguiContext;
+use bevy_inspector_egui::egui;
+use bevy_panorbit_camera::PanOrbitCamera;
+use bevy_window::PrimaryWindow;
+use graph::prelude::*;
+
+use crate::events::{ComponentsEvent, SelectIdentifiersEvent};
+use crate::graph_index::GraphIndex;
+use crate::history::History;
+use crate::identifiers::{Connection, Hidden, Identifier, Pinned};
+use crate::jobs::{poll, Job, Jobs};
+use crate::layout::{ease_identifiers_to, golden_angle_direction};
+use crate::resources::Configuration;
+use crate::util::calculate_from_translation_and_focus;
+
+/// Distance between neighbouring isolates when they are arranged.
+const ISOLATE_SPACING: f32 = 0.4;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum ComponentKind {
+    /// Connected when connection direction is ignored.
+    #[default]
+    Weak,
+    /// Every identifier reaches every other along connection direction.
+    Strong,
+}
+
+/// Where identifiers without connections go when the graph is laid out.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect, Resource)]
+#[reflect(Resource)]
+pub enum IsolatePolicy {
+    /// Left where they are.
+    Leave,
+    /// A square grid beside the graph.
+    #[default]
+    Grid,
+    /// A tightly packed disc beside the graph.
+    Pack,
+}
+
+impl IsolatePolicy {
+    pub const ALL: [IsolatePolicy; 3] = [
+        IsolatePolicy::Leave,
+        IsolatePolicy::Grid,
+        IsolatePolicy::Pack,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            IsolatePolicy::Leave => "Leave in place",
+            IsolatePolicy::Grid => "Grid",
+            IsolatePolicy::Pack => "Pack",
+        }
+    }
+}
+
+/// Components found by the last computation, largest first. Forgotten when
+/// identifiers or connections are added or removed.
+#[derive(Resource, Default)]
+pub struct ConnectedComponents {
+    pub kind: ComponentKind,
+    pub components: Vec<Vec<Entity>>,
+    pub hidden: Vec<bool>,
+}
+
+pub struct ConnectivityPlugin;
+
+impl Plugin for ConnectivityPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ConnectedComponents>()
+            .init_resource::<IsolatePolicy>()
+            .register_type::<IsolatePolicy>()
+            .add_systems(Update, compute_components)
+            .add_systems(Update, component_actions);
+    }
+}
+
+pub fn components_ui(
+    query: Query<&mut EguiContext, With<PrimaryWindow>>,
+    mut kind: Local<ComponentKind>,
+    mut policy: ResMut<IsolatePolicy>,
+    components: Res<ConnectedComponents>,
+    mut ev: EventWriter<ComponentsEvent>,
+) {
+    let mut egui_context = query.single().clone();
+
+    egui::Window::new("Components")
+        .vscroll(false)
+        .hscroll(false)
+        .default_width(250.0)
+        .resizable(false)
+        .show(egui_context.get_mut(), |ui| {
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut *kind, ComponentKind::Weak, "Weak");
+                ui.radio_value(&mut *kind, ComponentKind::Strong, "Strong");
+                if ui.button("Compute").clicked() {
+                    ev.send(ComponentsEvent::Compute(*kind));
+                }
+            });
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_label("Isolates")
+                    .selected_text(policy.name())
+                    .show_ui(ui, |ui| {
+                        for option in IsolatePolicy::ALL {
+                            ui.selectable_value(&mut *policy, option, option.name());
+                        }
+                    });
+                if ui.button("Arrange").clicked() {
+                    ev.send(ComponentsEvent::ArrangeIsolates);
+                }
+            });
+
+            if components.components.is_empty() {
+                return;
+            }
+            ui.separator();
+            let isolates = components
+                .components
+                .iter()
+                .filter(|members| members.len() == 1)
+                .count();
+            ui.label(format!(
+                "{} {} components, {} of a single identifier",
+                components.components.len(),
+                match components.kind {
+                    ComponentKind::Weak => "weakly connected",
+                    ComponentKind::Strong => "strongly connected",
+                },
+                isolates
+            ));
+            egui::ScrollArea::vertical()
+                .max_height(200.0)
+                .show(ui, |ui| {
+                    for (i, members) in components.components.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.label(format!("{}: {} identifiers", i, members.len()));
+                            if ui.small_button("Select").clicked() {
+                                ev.send(ComponentsEvent::Select(i));
+                            }
+                            if ui.small_button("Focus").clicked() {
+                                ev.send(ComponentsEvent::Focus(i));
+                            }
+                            if components.hidden[i] {
+                                if ui.small_button("Show").clicked() {
+                                    ev.send(ComponentsEvent::Show(i));
+                                }
+                            } else if ui.small_button("Hide").clicked() {
+                                ev.send(ComponentsEvent::Hide(i));
+                            }
+                        });
+                    }
+                });
+        });
+}
+
+fn compute_components(
+    mut ev: EventReader<ComponentsEvent>,
+    mut job: Local<Option<Job<(ComponentKind, Vec<Vec<Entity>>)>>>,
+    mut jobs: ResMut<Jobs>,
+    mut components: ResMut<ConnectedComponents>,
+    graph_index: Res<GraphIndex>,
+) {
+    if graph_index.is_changed() {
+        *job = None;
+        if !components.components.is_empty() {
+            *components = ConnectedComponents {
+                kind: components.kind,
+                ..default()
+            };
+        }
+    }
+
+    for e in ev.read() {
+        let ComponentsEvent::Compute(kind) = *e else {
+            continue;
+        };
+        let n = graph_index.len();
+        if n == 0 {
+            *job = None;
+            *components = ConnectedComponents { kind, ..default() };
+            continue;
+        }
+        let edges = graph_index.edges();
+        let entities: Vec<Entity> = (0..n).map(|i| graph_index.entity(i)).collect();
+        *job = Some(jobs.spawn("Components", move |_| {
+            let graph: DirectedCsrGraph<usize> = GraphBuilder::new()
+                .edges(edges)
+                .node_values(vec![(); n])
+                .build();
+            let labels = match kind {
+                ComponentKind::Weak => wcc_afforest_dss(&graph, WccConfig::default()).to_vec(),
+                ComponentKind::Strong => strongly_connected(&graph),
+            };
+            let mut members: Vec<Vec<Entity>> = vec![vec![]; n];
+            for (entity, label) in entities.into_iter().zip(labels) {
+                members[label].push(entity);
+            }
+            members.retain(|members| !members.is_empty());
+            members.sort_by_key(|members| std::cmp::Reverse(members.len()));
+            (kind, members)
+        }));
+    }
+
+    if let Some((kind, found)) = poll(&mut job) {
+        *components = ConnectedComponents {
+            kind,
+            hidden: vec![false; found.len()],
+            components: found,
+        };
+    }
+}
+
+fn component_actions(
+    mut commands: Commands,
+    mut ev: EventReader<ComponentsEvent>,
+    mut components: ResMut<ConnectedComponents>,
+    mut ev_select: EventWriter<SelectIdentifiersEvent>,
+    mut history: ResMut<History>,
+    policy: Res<IsolatePolicy>,
+    configuration: Res<Configuration>,
+    graph_index: Res<GraphIndex>,
+    identifier_query: Query<(&Transform, Has<Pinned>), With<Identifier>>,
+    connection_query: Query<(Entity, &Connection)>,
+    mut camera_q: Query<&mut PanOrbitCamera, With<PanOrbitCamera>>,
+) {
+    for e in ev.read() {
+        match *e {
+            ComponentsEvent::Compute(_) => {}
+            ComponentsEvent::Select(i) => {
+                let Some(members) = components.components.get(i) else {
+                    continue;
+                };
+                ev_select.send(SelectIdentifiersEvent {
+                    entities: members
+                        .iter()
+                        .copied()
+                        .filter(|&entity| identifier_query.contains(entity))
+                        .collect(),
+                    add: false,
+                });
+            }
+            ComponentsEvent::Focus(i) => {
+                let Some(members) = components.components.get(i) else {
+                    continue;
+                };
+                let positions: Vec<Vec3> = members
+                    .iter()
+                    .filter_map(|&entity| Some(identifier_query.get(entity).ok()?.0.translation))
+                    .collect();
+                if positions.is_empty() {
+                    continue;
+                }
+                let center = positions.iter().copied().sum::<Vec3>() / positions.len() as f32;
+                let radius = positions
+                    .iter()
+                    .map(|position| position.distance(center))
+                    .fold(0.0, f32::max);
+                if let Ok(mut camera) = camera_q.get_single_mut() {
+                    let (alpha, beta, radius) = calculate_from_translation_and_focus(
+                        center + Vec3::new(0.0, 0.0, radius * 2.0 + 2.0),
+                        center,
+                    );
+                    camera.target_alpha = alpha;
+                    camera.target_beta = beta;
+                    camera.target_radius = radius;
+                    camera.target_focus = center;
+                }
+            }
+            ComponentsEvent::Hide(i) | ComponentsEvent::Show(i) => {
+                let hide = matches!(e, ComponentsEvent::Hide(_));
+                let Some(members) = components.components.get(i) else {
+                    continue;
+                };
+                let visibility = if hide {
+                    Visibility::Hidden
+                } else {
+                    Visibility::Visible
+                };
+                for &entity in members {
+                    if let Some(mut entity) = commands.get_entity(entity) {
+                        if hide {
+                            entity.insert((Hidden, visibility));
+                        } else {
+                            entity.remove::<Hidden>().insert(visibility);
+                        }
+                    }
+                }
+                for (entity, connection) in connection_query.iter() {
+                    if members.contains(&connection.from) || members.contains(&connection.to) {
+                        commands.entity(entity).insert(visibility);
+                    }
+                }
+                components.hidden[i] = hide;
+            }
+            ComponentsEvent::ArrangeIsolates => {
+                let isolated = isolated(&graph_index);
+                let occupied = (0..graph_index.len())
+                    .filter(|&i| !isolated[i])
+                    .filter_map(|i| identifier_query.get(graph_index.entity(i)).ok())
+                    .map(|(transform, _)| transform.translation);
+                let isolates: Vec<Entity> = (0..graph_index.len())
+                    .filter(|&i| isolated[i])
+                    .map(|i| graph_index.entity(i))
+                    .collect();
+                let positions = isolate_positions(*policy, occupied, isolates.len());
+                ease_identifiers_to(
+                    &mut commands,
+                    &mut history,
+                    &configuration,
+                    &identifier_query,
+                    isolates.into_iter().zip(positions),
+                );
+            }
+        }
+    }
+}
+
+/// Whether each identifier in `graph_index` has no connection to another one.
+pub fn isolated(graph_index: &GraphIndex) -> Vec<bool> {
+    let mut isolated = vec![true; graph_index.len()];
+    for (from, to) in graph_index.edges() {
+        if from != to {
+            isolated[from] = false;
+            isolated[to] = false;
+        }
+    }
+    isolated
+}
+
+/// Positions for `count` isolates beside the identifiers at `occupied`, on
+/// the positive X side and centred on them vertically.
+pub fn isolate_positions(
+    policy: IsolatePolicy,
+    occupied: impl Iterator<Item = Vec3>,
+    count: usize,
+) -> Vec<Vec3> {
+    let (min, max) = occupied.fold(
+        (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+        |(min, max), position| (min.min(position), max.max(position)),
+    );
+    let (right, center) = if min.x.is_finite() {
+        (max.x, (min + max) / 2.0)
+    } else {
+        (0.0, Vec3::ZERO)
+    };
+    let left = right + 2.0 * ISOLATE_SPACING;
+
+    match policy {
+        IsolatePolicy::Leave => vec![],
+        IsolatePolicy::Grid => {
+            let columns = (count as f32).sqrt().ceil().max(1.0) as usize;
+            let rows = count.div_ceil(columns);
+            let top = center.y + (rows.saturating_sub(1)) as f32 * ISOLATE_SPACING / 2.0;
+            (0..count)
+                .map(|i| {
+                    Vec3::new(
+                        left + (i % columns) as f32 * ISOLATE_SPACING,
+                        top - (i / columns) as f32 * ISOLATE_SPACING,
+                        center.z,
+                    )
+                })
+                .collect()
+        }
+        IsolatePolicy::Pack => {
+            // Vogel's sunflower spiral: every point gets about the same area.
+            let step = ISOLATE_SPACING * 0.55;
+            let radius = step * (count as f32).sqrt();
+            (0..count)
+                .map(|i| {
+                    let offset = golden_angle_direction(i) * step * (i as f32 + 0.5).sqrt();
+                    Vec3::new(left + radius + offset.x, center.y + offset.y, center.z)
+                })
+                .collect()
+        }
+    }
+}
+
+/// Tarjan's strongly connected components, with an explicit stack so long
+/// chains do not overflow the call stack. Returns a component label per node.
+fn strongly_connected(graph: &DirectedCsrGraph<usize>) -> Vec<usize> {
+    let n = graph.node_count();
+    let mut index = vec![usize::MAX; n];
+    let mut low_link = vec![0; n];
+    let mut on_stack = vec![false; n];
+    let mut stack = vec![];
+    let mut labels = vec![usize::MAX; n];
+    let mut next_index = 0;
+    let mut next_label = 0;
+
+    for root in 0..n {
+        if index[root] != usize::MAX {
+            continue;
+        }
+        // (node, position in its neighbour list)
+        let mut call_stack = vec![(root, 0)];
+        index[root] = next_index;
+        low_link[root] = next_index;
+        next_index += 1;
+        stack.push(root);
+        on_stack[root] = true;
+
+        while let Some(&mut (v, ref mut position)) = call_stack.last_mut() {
+            if let Some(&w) = graph.out_neighbors(v).nth(*position) {
+                *position += 1;
+                if index[w] == usize::MAX {
+                    index[w] = next_index;
+                    low_link[w] = next_index;
+                    next_index += 1;
+                    stack.push(w);
+                    on_stack[w] = true;
+                    call_stack.push((w, 0));
+                } else if on_stack[w] {
+                    low_link[v] = low_link[v].min(index[w]);
+                }
+                continue;
+            }
+
+            call_stack.pop();
+            if let Some(&(parent, _)) = call_stack.last() {
+                low_link[parent] = low_link[parent].min(low_link[v]);
+            }
+            if low_link[v] == index[v] {
+                while let Some(w) = stack.pop() {
+                    on_stack[w] = false;
+                    labels[w] = next_label;
+                    if w == v {
+                        break;
+                    }
+                }
+                next_label += 1;
+            }
+        }
+    }
+    labels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strongly_connected_cycle_with_tail() {
+        // 0 -> 1 -> 2 -> 0, then 2 -> 3 -> 4
+        let graph: DirectedCsrGraph<usize> = GraphBuilder::new()
+            .edges(vec![(0, 1), (1, 2), (2, 0), (2, 3), (3, 4)])
+            .node_values(vec![(); 5])
+            .build();
+        let labels = strongly_connected(&graph);
+        assert_eq!(labels[0], labels[1]);
+        assert_eq!(labels[0], labels[2]);
+        assert_ne!(labels[0], labels[3]);
+        assert_ne!(labels[0], labels[4]);
+        assert_ne!(labels[3], labels[4]);
+        assert!(labels.iter().all(|&label| label < 3));
+    }
+
+    #[test]
+    fn isolates_in_a_grid_beside_the_graph() {
+        let occupied = [Vec3::new(-1.0, -1.0, 0.0), Vec3::new(1.0, 1.0, 2.0)];
+        let positions = isolate_positions(IsolatePolicy::Grid, occupied.into_iter(), 4);
+        let expected = [
+            Vec3::new(1.8, 0.2, 1.0),
+            Vec3::new(2.2, 0.2, 1.0),
+            Vec3::new(1.8, -0.2, 1.0),
+            Vec3::new(2.2, -0.2, 1.0),
+        ];
+        for (position, expected) in positions.iter().zip(expected) {
+            assert!(position.abs_diff_eq(expected, 1e-5), "{:?}", positions);
+        }
+        assert_eq!(positions.len(), 4);
+
+        // Without a graph they start next to the origin.
+        let positions = isolate_positions(IsolatePolicy::Grid, std::iter::empty(), 1);
+        assert!(positions[0].abs_diff_eq(Vec3::new(0.8, 0.0, 0.0), 1e-5));
+
+        assert!(isolate_positions(IsolatePolicy::Leave, occupied.into_iter(), 4).is_empty());
+    }
+
+    #[test]
+    fn packed_isolates_stay_clear_of_the_graph() {
+        let occupied = [Vec3::new(-1.0, -1.0, 0.0), Vec3::new(1.0, 1.0, 0.0)];
+        let positions = isolate_positions(IsolatePolicy::Pack, occupied.into_iter(), 20);
+        assert_eq!(positions.len(), 20);
+        assert!(positions.iter().all(|position| position.x > 1.0));
+        for (i, a) in positions.iter().enumerate() {
+            for b in positions[i + 1..].iter() {
+                assert!(a.distance(*b) > ISOLATE_SPACING * 0.3);
+            }
+        }
+    }
+}
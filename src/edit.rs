@@ -0,0 +1,240 @@
+use bevy::prelude::*;
+use bevy_egui::EguiContext;
+use bevy_inspector_egui::egui;
+use bevy_mod_picking::focus::HoverMap;
+use bevy_mod_picking::prelude::*;
+use bevy_panorbit_camera::PanOrbitCamera;
+use bevy_window::PrimaryWindow;
+use rand::Rng;
+
+use crate::assets::MyAssets;
+use crate::events::SelectionEvent;
+use crate::identifiers::{
+    spawn_connection, spawn_identifier, Connection, ConnectionKind, Identifier, IDENTIFIER_SCALE,
+};
+use crate::resources::{Configuration, SeededRng};
+
+/// Distance in logical pixels between press and release for a click in empty
+/// space; longer drags orbit the camera instead.
+const CLICK_DISTANCE: f32 = 5.0;
+
+/// While enabled, clicking empty space creates an identifier, dragging from one
+/// identifier to another connects them and Delete removes the selection.
+#[derive(Reflect, Resource, Default)]
+#[reflect(Resource)]
+pub struct EditMode {
+    pub enabled: bool,
+}
+
+pub struct EditPlugin;
+
+impl Plugin for EditPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EditMode>()
+            .register_type::<EditMode>()
+            .add_systems(
+                Update,
+                (create_identifier, delete_selected_identifiers)
+                    .run_if(|edit_mode: Res<EditMode>| edit_mode.enabled),
+            )
+            // Runs outside edit mode too, to give the camera back when edit mode
+            // is turned off halfway through a drag.
+            .add_systems(Update, connect_identifiers);
+    }
+}
+
+fn create_identifier(
+    mut commands: Commands,
+    mouse: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    hover_map: Res<HoverMap>,
+    // Where the left button went down in empty space
+    mut pressed_at: Local<Option<Vec2>>,
+    window_query: Query<(&Window, &EguiContext), With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform, &PanOrbitCamera)>,
+    my_assets: Res<MyAssets>,
+    configuration: Res<Configuration>,
+    mut seeded_rng: ResMut<SeededRng>,
+) {
+    let Ok((window, egui_context)) = window_query.get_single() else {
+        return;
+    };
+    let Some(cursor) = window.cursor_position() else {
+        return;
+    };
+    if mouse.just_pressed(MouseButton::Left) {
+        let hovering = hover_map
+            .get(&PointerId::Mouse)
+            .is_some_and(|hits| !hits.is_empty());
+        // Shift and ctrl clicks select.
+        let modifiers = keys.any_pressed([
+            KeyCode::ShiftLeft,
+            KeyCode::ShiftRight,
+            KeyCode::ControlLeft,
+            KeyCode::ControlRight,
+        ]);
+        let over_ui = egui_context.clone().get_mut().is_pointer_over_area();
+        *pressed_at = (!hovering && !modifiers && !over_ui).then_some(cursor);
+    }
+    if !mouse.just_released(MouseButton::Left) {
+        return;
+    }
+    if !pressed_at
+        .take()
+        .is_some_and(|start| start.distance(cursor) < CLICK_DISTANCE)
+    {
+        return;
+    }
+
+    let Ok((camera, camera_transform, pan_orbit)) = camera_query.get_single() else {
+        return;
+    };
+    // On the plane through the camera focus that faces the camera
+    let normal = (pan_orbit.focus - camera_transform.translation()).normalize();
+    let Some(position) = camera
+        .viewport_to_world(camera_transform, cursor)
+        .and_then(|ray| Some(ray.get_point(ray.intersect_plane(pan_orbit.focus, normal)?)))
+    else {
+        return;
+    };
+    let entity = spawn_identifier(
+        &mut commands,
+        &my_assets,
+        &configuration,
+        Identifier {
+            key: format!("did:example:{:016x}", seeded_rng.rng().gen::<u64>()),
+            label: None,
+        },
+        position,
+        IDENTIFIER_SCALE,
+    );
+    info!("Created identifier {:?}", entity);
+}
+
+fn connect_identifiers(
+    mut commands: Commands,
+    mut ev_start: EventReader<Pointer<DragStart>>,
+    mut ev_drop: EventReader<Pointer<Drop>>,
+    mut ev_end: EventReader<Pointer<DragEnd>>,
+    // Identifier the connection is dragged from
+    mut source: Local<Option<Entity>>,
+    window_query: Query<(&Window, &EguiContext), With<PrimaryWindow>>,
+    mut camera_query: Query<(&Camera, &GlobalTransform, &mut PanOrbitCamera)>,
+    identifier_query: Query<&GlobalTransform, With<Identifier>>,
+    connection_query: Query<&Connection>,
+    my_assets: Res<MyAssets>,
+    configuration: Res<Configuration>,
+    edit_mode: Res<EditMode>,
+) {
+    let Ok((camera, camera_transform, mut pan_orbit)) = camera_query.get_single_mut() else {
+        return;
+    };
+    if !edit_mode.enabled {
+        ev_start.clear();
+        ev_drop.clear();
+        ev_end.clear();
+        if source.take().is_some() {
+            pan_orbit.enabled = true;
+        }
+        return;
+    }
+
+    for event in ev_start.read() {
+        if event.button == PointerButton::Primary && identifier_query.contains(event.target) {
+            *source = Some(event.target);
+            pan_orbit.enabled = false;
+        }
+    }
+    for event in ev_drop.read() {
+        let (from, to) = (event.dropped, event.target);
+        if *source != Some(from) || from == to {
+            continue;
+        }
+        let (Ok(from_transform), Ok(to_transform)) =
+            (identifier_query.get(from), identifier_query.get(to))
+        else {
+            continue;
+        };
+        if connection_query
+            .iter()
+            .any(|connection| connection.from == from && connection.to == to)
+        {
+            continue;
+        }
+        let entity = spawn_connection(
+            &mut commands,
+            &my_assets,
+            &configuration,
+            Connection {
+                from,
+                to,
+                weight: 1.0,
+                kind: ConnectionKind::Generic,
+            },
+            from_transform.translation(),
+            to_transform.translation(),
+        );
+        info!("Connected {:?} to {:?} with {:?}", from, to, entity);
+    }
+    for event in ev_end.read() {
+        if *source == Some(event.target) {
+            *source = None;
+            pan_orbit.enabled = true;
+        }
+    }
+
+    // Rubber band from the source to the pointer
+    let Some(from) = *source else {
+        return;
+    };
+    if !identifier_query.contains(from) {
+        // Deleted or undone mid-drag
+        *source = None;
+        pan_orbit.enabled = true;
+        return;
+    }
+    let Ok((window, egui_context)) = window_query.get_single() else {
+        return;
+    };
+    let (Some(start), Some(end)) = (
+        identifier_query.get(from).ok().and_then(|transform| {
+            camera.world_to_viewport(camera_transform, transform.translation())
+        }),
+        window.cursor_position(),
+    ) else {
+        return;
+    };
+    let mut egui_context = egui_context.clone();
+    let ctx = egui_context.get_mut();
+    // Logical pixels to egui points
+    let scale = window.scale_factor() as f32 / ctx.pixels_per_point();
+    ctx.layer_painter(egui::LayerId::new(
+        egui::Order::Foreground,
+        egui::Id::new("connection"),
+    ))
+    .line_segment(
+        [
+            egui::pos2(start.x * scale, start.y * scale),
+            egui::pos2(end.x * scale, end.y * scale),
+        ],
+        egui::Stroke::new(2.0f32, egui::Color32::WHITE),
+    );
+}
+
+fn delete_selected_identifiers(
+    keys: Res<Input<KeyCode>>,
+    window_query: Query<&EguiContext, With<PrimaryWindow>>,
+    mut ev: EventWriter<SelectionEvent>,
+) {
+    if !keys.any_just_pressed([KeyCode::Delete, KeyCode::Back]) {
+        return;
+    }
+    // Not while typing in a text field
+    if window_query
+        .get_single()
+        .is_ok_and(|egui_context| egui_context.clone().get_mut().wants_keyboard_input())
+    {
+        return;
+    }
+    ev.send(SelectionEvent::Delete);
+}
@@ -1,6 +1,13 @@
 use bevy::prelude::*;
-use forceatlas2::Settings;
 use graph::page_rank::PageRankConfig;
+use std::path::PathBuf;
+
+use crate::communities::CommunityAlgorithm;
+use crate::connectivity::ComponentKind;
+use crate::formats::GraphFormat;
+use crate::layout::LayoutSettings;
+use crate::metrics::Metric;
+use crate::simulation::ForceAtlas2Settings;
 
 #[derive(Event, Debug)]
 pub struct SelectRandomIdentifierEvent;
@@ -11,6 +18,53 @@ pub struct SelectIdentifierEvent(pub Entity);
 #[derive(Event, Debug)]
 pub struct DeselectIdentifierEvent;
 
+/// Adds the identifier to the selection, or removes it when it is already selected.
+#[derive(Event, Debug)]
+pub struct ToggleSelectionEvent(pub Entity);
+
+#[derive(Event, Debug)]
+pub struct SelectIdentifiersEvent {
+    pub entities: Vec<Entity>,
+    /// Add to the current selection instead of replacing it.
+    pub add: bool,
+}
+
+/// Lets layouts move every pinned identifier again.
+#[derive(Event, Debug)]
+pub struct UnpinAllEvent;
+
+/// Highlights one more hop around the selection.
+#[derive(Event, Debug)]
+pub struct ExpandNeighbourhoodEvent;
+
+/// Despawns identifiers and their connections as one edit that can be undone.
+#[derive(Event, Debug)]
+pub struct DeleteIdentifiersEvent(pub Vec<Entity>);
+
+#[derive(Event, Debug)]
+pub struct UndoEvent;
+
+#[derive(Event, Debug)]
+pub struct RedoEvent;
+
+/// Bulk actions on the selected identifiers.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionEvent {
+    Hide,
+    /// Shows every hidden identifier, selected or not.
+    ShowAll,
+    Delete,
+    Pin,
+    Unpin,
+}
+
+/// Picks the identifier the shortest path from the selected one leads to.
+#[derive(Event, Debug)]
+pub struct SelectTargetIdentifierEvent(pub Entity);
+
+#[derive(Event, Debug)]
+pub struct DeselectTargetIdentifierEvent;
+
 #[derive(Event, Debug)]
 pub struct SelectRandomConnectedIdentifierEvent;
 
@@ -29,12 +83,87 @@ pub struct AddConnectionsEvent {
 
 #[derive(Event)]
 pub struct Forceatlas2Event {
-    pub settings: Settings<f32>,
-    pub iterations: u32,
+    pub settings: ForceAtlas2Settings,
+}
+/// Controls the ForceAtlas2 layout that runs a few iterations every frame.
+#[derive(Event)]
+pub enum Forceatlas2LiveEvent {
+    /// Starts or resumes the layout, or applies new settings while it runs.
+    Start {
+        settings: ForceAtlas2Settings,
+    },
+    Pause,
+    Stop,
+}
+
+/// Runs one of the layouts from the Layout window and eases identifiers to the result.
+#[derive(Event)]
+pub struct LayoutEvent {
+    pub settings: LayoutSettings,
 }
+
+/// Computes every centrality over the current graph.
 #[derive(Event)]
-pub struct PageRankEvent {
-    pub config: PageRankConfig,
+pub struct MetricsEvent {
+    pub page_rank: PageRankConfig,
+    /// Metric identifiers are sized by once everything is computed.
+    pub size_by: Metric,
+}
+
+#[derive(Event, Debug)]
+pub struct DetectCommunitiesEvent {
+    pub algorithm: CommunityAlgorithm,
+}
+
+/// Removes community colours, back to the default material.
+#[derive(Event, Debug)]
+pub struct ClearCommunitiesEvent;
+
+/// Computes connected components and acts on the ones listed in the Components window,
+/// which are numbered from the largest down.
+#[derive(Event, Debug)]
+pub enum ComponentsEvent {
+    Compute(ComponentKind),
+    /// Selects every identifier of a component.
+    Select(usize),
+    Focus(usize),
+    Hide(usize),
+    Show(usize),
+    /// Places identifiers without connections by the isolate policy.
+    ArrangeIsolates,
+}
+
+/// Sizes identifiers by a metric computed earlier.
+#[derive(Event, Debug)]
+pub struct SizeByMetricEvent(pub Metric);
+
+#[derive(Event, Debug)]
+pub struct ImportGraphEvent {
+    pub path: PathBuf,
+    /// Guessed from the file extension when `None`.
+    pub format: Option<GraphFormat>,
+    /// Despawn the current identifiers and connections first.
+    pub replace: bool,
+}
+
+/// Writes the graph, camera, selection and settings to a session file.
+#[derive(Event, Debug)]
+pub struct SaveSessionEvent {
+    pub path: PathBuf,
+}
+
+/// Replaces the graph, camera, selection and settings with those in a session file.
+#[derive(Event, Debug)]
+pub struct OpenSessionEvent {
+    pub path: PathBuf,
+}
+
+#[derive(Event, Debug)]
+pub struct ExportGraphEvent {
+    pub path: PathBuf,
+    pub format: GraphFormat,
+    /// Only the selected identifiers and the connections between them.
+    pub selected_only: bool,
 }
 
 pub struct EventsPlugin;
@@ -45,10 +174,30 @@ impl Plugin for EventsPlugin {
             .add_event::<SelectRandomConnectedIdentifierEvent>()
             .add_event::<SelectIdentifierEvent>()
             .add_event::<DeselectIdentifierEvent>()
+            .add_event::<ToggleSelectionEvent>()
+            .add_event::<SelectIdentifiersEvent>()
+            .add_event::<SelectionEvent>()
+            .add_event::<ExpandNeighbourhoodEvent>()
+            .add_event::<UnpinAllEvent>()
+            .add_event::<DeleteIdentifiersEvent>()
+            .add_event::<UndoEvent>()
+            .add_event::<RedoEvent>()
+            .add_event::<SelectTargetIdentifierEvent>()
+            .add_event::<DeselectTargetIdentifierEvent>()
             .add_event::<AddIdentifiersEvent>()
             .add_event::<MoveIdentifiersRndEvent>()
             .add_event::<Forceatlas2Event>()
-            .add_event::<PageRankEvent>()
-            .add_event::<AddConnectionsEvent>();
+            .add_event::<Forceatlas2LiveEvent>()
+            .add_event::<LayoutEvent>()
+            .add_event::<MetricsEvent>()
+            .add_event::<SizeByMetricEvent>()
+            .add_event::<DetectCommunitiesEvent>()
+            .add_event::<ClearCommunitiesEvent>()
+            .add_event::<ComponentsEvent>()
+            .add_event::<AddConnectionsEvent>()
+            .add_event::<ImportGraphEvent>()
+            .add_event::<ExportGraphEvent>()
+            .add_event::<SaveSessionEvent>()
+            .add_event::<OpenSessionEvent>();
     }
 }
@@ -0,0 +1,138 @@
+use std::collections::BTreeMap;
+
+use super::{EdgeData, GraphData, ImportError};
+use crate::identifiers::ConnectionKind;
+
+const SOURCE_HEADERS: [&str; 3] = ["source", "from", "src"];
+const TARGET_HEADERS: [&str; 3] = ["target", "to", "dst"];
+
+/// Reads `source,target[,weight,...]` rows. A header row is optional and, when
+/// present, is used to find the `weight` column; otherwise the third column is
+/// taken as the weight. Columns after that are ignored.
+pub fn read(contents: &[u8], delimiter: u8) -> Result<GraphData, ImportError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .comment(Some(b'#'))
+        .from_reader(contents);
+
+    let mut graph = GraphData::default();
+    let mut weight_column = Some(2);
+
+    for (i, record) in reader.records().enumerate() {
+        let record = match record {
+            Ok(record) => record,
+            Err(err) => {
+                graph.warn(format!("row {}: {}", i + 1, err));
+                continue;
+            }
+        };
+        let line = record.position().map_or(i as u64 + 1, |p| p.line());
+
+        if i == 0 && is_header(&record) {
+            weight_column = record
+                .iter()
+                .position(|field| field.eq_ignore_ascii_case("weight"));
+            continue;
+        }
+
+        if record.len() < 2 {
+            graph.warn(format!(
+                "line {}: expected at least 2 columns, found {}",
+                line,
+                record.len()
+            ));
+            continue;
+        }
+
+        let (source, target) = (&record[0], &record[1]);
+        if source.is_empty() || target.is_empty() {
+            graph.warn(format!("line {}: empty source or target", line));
+            continue;
+        }
+
+        let weight = match weight_column.and_then(|column| record.get(column)) {
+            None | Some("") => 1.0,
+            Some(field) => match field.parse::<f32>() {
+                Ok(weight) if weight.is_finite() => weight,
+                _ => {
+                    graph.warn(format!("line {}: invalid weight {:?}", line, field));
+                    continue;
+                }
+            },
+        };
+
+        let from = graph.node(source);
+        let to = graph.node(target);
+        if from == to {
+            graph.warn(format!("line {}: skipping self-loop on {}", line, source));
+            continue;
+        }
+
+        graph.edges.push(EdgeData {
+            from,
+            to,
+            weight,
+            kind: ConnectionKind::Generic,
+            attributes: BTreeMap::new(),
+        });
+    }
+
+    if graph.nodes.is_empty() {
+        return Err(ImportError::Parse("no edges found".to_string()));
+    }
+
+    Ok(graph)
+}
+
+fn is_header(record: &csv::StringRecord) -> bool {
+    let matches = |field: Option<&str>, names: &[&str]| {
+        field.is_some_and(|field| names.iter().any(|name| field.eq_ignore_ascii_case(name)))
+    };
+    matches(record.get(0), &SOURCE_HEADERS) && matches(record.get(1), &TARGET_HEADERS)
+}
+
+pub fn write(graph: &GraphData, delimiter: u8) -> Result<String, csv::Error> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(vec![]);
+    writer.write_record(["source", "target", "weight"])?;
+    for edge in graph.edges.iter() {
+        writer.write_record([
+            graph.nodes[edge.from].key.as_str(),
+            graph.nodes[edge.to].key.as_str(),
+            edge.weight.to_string().as_str(),
+        ])?;
+    }
+    let bytes = writer.into_inner().map_err(|err| err.into_error())?;
+    Ok(String::from_utf8_lossy(&bytes).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{edge_list, sample_graph};
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let graph = sample_graph();
+        for &delimiter in b",\t" {
+            let text = write(&graph, delimiter).unwrap();
+            let read = read(text.as_bytes(), delimiter).unwrap();
+            assert!(read.warnings.is_empty(), "{:?}", read.warnings);
+            let keys: Vec<&str> = read.nodes.iter().map(|node| node.key.as_str()).collect();
+            let expected: Vec<&str> = graph.nodes.iter().map(|node| node.key.as_str()).collect();
+            assert_eq!(keys, expected);
+            assert_eq!(edge_list(&read), edge_list(&graph));
+        }
+    }
+
+    #[test]
+    fn without_header() {
+        let read = read(b"a,b\nb,c,2\n# comment\nc,c\nd\n", b',').unwrap();
+        assert_eq!(edge_list(&read), vec![("a", "b", 1.0), ("b", "c", 2.0)]);
+        assert_eq!(read.warnings.len(), 2);
+    }
+}
@@ -0,0 +1,439 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::{self, Write};
+
+use bevy::math::Vec3;
+use bevy::render::color::Color;
+use quick_xml::escape::escape;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+
+use super::{xml_attribute, EdgeData, GraphData, ImportError, LABEL_ATTRIBUTE};
+use crate::identifiers::ConnectionKind;
+
+struct AttributeDef {
+    title: String,
+    default: Option<String>,
+}
+
+enum Element {
+    Node(usize),
+    Edge(EdgeData),
+    SkippedEdge,
+    Other,
+}
+
+/// Reads GEXF 1.1 - 1.3, including the `viz:position`, `viz:size` and
+/// `viz:color` extensions Gephi writes.
+pub fn read(text: &str) -> Result<GraphData, ImportError> {
+    let mut reader = Reader::from_str(text);
+    reader.trim_text(true);
+
+    let mut graph = GraphData::default();
+    // attribute definitions per class ("node" / "edge"), by id
+    let mut definitions: HashMap<(String, String), AttributeDef> = HashMap::new();
+    let mut class = String::new();
+    let mut element = Element::Other;
+    // `<attribute>` whose `<default>` is being read
+    let mut definition: Option<(String, String)> = None;
+    let mut in_default = false;
+
+    loop {
+        let event = reader.read_event().map_err(|err| {
+            ImportError::Parse(format!(
+                "XML error at byte {}: {}",
+                reader.buffer_position(),
+                err
+            ))
+        })?;
+
+        match event {
+            Event::Start(ref e) | Event::Empty(ref e) => {
+                let empty = matches!(event, Event::Empty(_));
+                match e.local_name().as_ref() {
+                    b"attributes" => {
+                        class = xml_attribute(e, b"class").unwrap_or_else(|| "node".to_string());
+                    }
+                    b"attribute" => {
+                        let Some(id) = xml_attribute(e, b"id") else {
+                            graph.warn("<attribute> without id".to_string());
+                            continue;
+                        };
+                        let key = (class.clone(), id.clone());
+                        definitions.insert(
+                            key.clone(),
+                            AttributeDef {
+                                title: xml_attribute(e, b"title").unwrap_or(id),
+                                default: None,
+                            },
+                        );
+                        if !empty {
+                            definition = Some(key);
+                        }
+                    }
+                    b"default" if !empty => in_default = definition.is_some(),
+                    b"node" => {
+                        let Some(id) = xml_attribute(e, b"id") else {
+                            graph.warn("<node> without id".to_string());
+                            continue;
+                        };
+                        let index = graph.node(&id);
+                        if let Some(label) = xml_attribute(e, b"label") {
+                            graph.nodes[index]
+                                .attributes
+                                .insert(LABEL_ATTRIBUTE.to_string(), label);
+                        }
+                        if empty {
+                            apply_defaults(
+                                &mut graph.nodes[index].attributes,
+                                &definitions,
+                                "node",
+                            );
+                        } else {
+                            element = Element::Node(index);
+                        }
+                    }
+                    b"edge" => {
+                        let edge = match (xml_attribute(e, b"source"), xml_attribute(e, b"target"))
+                        {
+                            (Some(source), Some(target)) if source == target => {
+                                graph.node(&source);
+                                graph.warn(format!("skipping self-loop on {}", source));
+                                None
+                            }
+                            (Some(source), Some(target)) => {
+                                let mut edge = EdgeData {
+                                    from: graph.node(&source),
+                                    to: graph.node(&target),
+                                    weight: 1.0,
+                                    kind: ConnectionKind::Generic,
+                                    attributes: BTreeMap::new(),
+                                };
+                                if let Some(label) = xml_attribute(e, b"label") {
+                                    edge.attributes.insert(LABEL_ATTRIBUTE.to_string(), label);
+                                }
+                                match xml_attribute(e, b"weight").map(|w| w.trim().parse::<f32>()) {
+                                    None => Some(edge),
+                                    Some(Ok(weight)) if weight.is_finite() => {
+                                        edge.weight = weight;
+                                        Some(edge)
+                                    }
+                                    Some(_) => {
+                                        graph.warn(format!(
+                                            "edge {} -> {}: invalid weight",
+                                            source, target
+                                        ));
+                                        None
+                                    }
+                                }
+                            }
+                            _ => {
+                                graph.warn("<edge> without source or target".to_string());
+                                None
+                            }
+                        };
+                        match (edge, empty) {
+                            (Some(mut edge), true) => {
+                                apply_defaults(&mut edge.attributes, &definitions, "edge");
+                                graph.edges.push(edge);
+                            }
+                            (Some(edge), false) => element = Element::Edge(edge),
+                            (None, true) => {}
+                            (None, false) => element = Element::SkippedEdge,
+                        }
+                    }
+                    b"attvalue" => {
+                        let (Some(id), Some(value)) = (
+                            xml_attribute(e, b"for").or_else(|| xml_attribute(e, b"id")),
+                            xml_attribute(e, b"value"),
+                        ) else {
+                            continue;
+                        };
+                        let domain = match element {
+                            Element::Node(_) => "node",
+                            Element::Edge(_) => "edge",
+                            Element::SkippedEdge | Element::Other => continue,
+                        };
+                        let title = definitions
+                            .get(&(domain.to_string(), id.clone()))
+                            .map_or(id, |definition| definition.title.clone());
+                        match &mut element {
+                            Element::Node(index) => {
+                                graph.nodes[*index].attributes.insert(title, value);
+                            }
+                            Element::Edge(edge) => {
+                                edge.attributes.insert(title, value);
+                            }
+                            Element::SkippedEdge | Element::Other => {}
+                        }
+                    }
+                    b"position" => {
+                        if let Element::Node(index) = element {
+                            let coordinate = |name: &[u8]| {
+                                xml_attribute(e, name).and_then(|v| v.trim().parse::<f32>().ok())
+                            };
+                            match (coordinate(b"x"), coordinate(b"y"), coordinate(b"z")) {
+                                (Some(x), Some(y), z) => {
+                                    graph.nodes[index].position =
+                                        Some(Vec3::new(x, y, z.unwrap_or(0.0)));
+                                }
+                                _ => graph.warn(format!(
+                                    "node {}: invalid viz:position",
+                                    graph.nodes[index].key
+                                )),
+                            }
+                        }
+                    }
+                    b"size" => {
+                        if let Element::Node(index) = element {
+                            match xml_attribute(e, b"value").map(|v| v.trim().parse::<f32>()) {
+                                Some(Ok(size)) if size > 0.0 => {
+                                    graph.nodes[index].scale = Some(size);
+                                }
+                                _ => graph.warn(format!(
+                                    "node {}: invalid viz:size",
+                                    graph.nodes[index].key
+                                )),
+                            }
+                        }
+                    }
+                    b"color" => {
+                        if let Element::Node(index) = element {
+                            match read_color(e) {
+                                Some(color) => graph.nodes[index].color = Some(color),
+                                None => graph.warn(format!(
+                                    "node {}: invalid viz:color",
+                                    graph.nodes[index].key
+                                )),
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Event::Text(e) => {
+                if in_default {
+                    let text = e
+                        .unescape()
+                        .map_err(|err| ImportError::Parse(err.to_string()))?;
+                    if let Some(definition) =
+                        definition.as_ref().and_then(|key| definitions.get_mut(key))
+                    {
+                        definition
+                            .default
+                            .get_or_insert_with(String::new)
+                            .push_str(&text);
+                    }
+                }
+            }
+            Event::End(e) => match e.local_name().as_ref() {
+                b"attribute" => definition = None,
+                b"default" => in_default = false,
+                b"node" => {
+                    if let Element::Node(index) = std::mem::replace(&mut element, Element::Other) {
+                        apply_defaults(&mut graph.nodes[index].attributes, &definitions, "node");
+                    }
+                }
+                b"edge" => {
+                    if let Element::Edge(mut edge) = std::mem::replace(&mut element, Element::Other)
+                    {
+                        apply_defaults(&mut edge.attributes, &definitions, "edge");
+                        graph.edges.push(edge);
+                    }
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    if graph.nodes.is_empty() {
+        return Err(ImportError::Parse("no nodes found".to_string()));
+    }
+
+    Ok(graph)
+}
+
+fn read_color(e: &quick_xml::events::BytesStart) -> Option<Color> {
+    if let Some(hex) = xml_attribute(e, b"hex") {
+        return Color::hex(hex.trim()).ok();
+    }
+    let channel = |name: &[u8]| xml_attribute(e, name).and_then(|v| v.trim().parse::<u8>().ok());
+    let alpha = match xml_attribute(e, b"a") {
+        Some(a) => a.trim().parse::<f32>().ok()?,
+        None => 1.0,
+    };
+    Some(Color::rgba_u8(channel(b"r")?, channel(b"g")?, channel(b"b")?, 255).with_a(alpha))
+}
+
+fn apply_defaults(
+    attributes: &mut BTreeMap<String, String>,
+    definitions: &HashMap<(String, String), AttributeDef>,
+    domain: &str,
+) {
+    for ((class, _), definition) in definitions.iter() {
+        if class != domain {
+            continue;
+        }
+        if let Some(default) = &definition.default {
+            attributes
+                .entry(definition.title.clone())
+                .or_insert_with(|| default.clone());
+        }
+    }
+}
+
+pub fn write(graph: &GraphData) -> Result<String, fmt::Error> {
+    let node_titles: BTreeSet<&str> = graph
+        .nodes
+        .iter()
+        .flat_map(|node| node.attributes.keys().map(String::as_str))
+        .filter(|title| *title != LABEL_ATTRIBUTE)
+        .collect();
+    let edge_titles: BTreeSet<&str> = graph
+        .edges
+        .iter()
+        .flat_map(|edge| edge.attributes.keys().map(String::as_str))
+        .filter(|title| *title != LABEL_ATTRIBUTE)
+        .collect();
+
+    let mut out = String::new();
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(
+        out,
+        "<gexf xmlns=\"http://gexf.net/1.3\" xmlns:viz=\"http://gexf.net/1.3/viz\" version=\"1.3\">"
+    )?;
+    writeln!(out, "  <graph defaultedgetype=\"directed\">")?;
+    let node_ids = declare_attributes(&mut out, "node", &node_titles)?;
+    let edge_ids = declare_attributes(&mut out, "edge", &edge_titles)?;
+
+    writeln!(out, "    <nodes>")?;
+    for node in graph.nodes.iter() {
+        write!(out, "      <node id=\"{}\"", escape(&node.key))?;
+        if let Some(label) = node.attributes.get(LABEL_ATTRIBUTE) {
+            write!(out, " label=\"{}\"", escape(label))?;
+        }
+        writeln!(out, ">")?;
+        write_attvalues(&mut out, &node_ids, &node.attributes)?;
+        if let Some(color) = node.color {
+            let [r, g, b, a] = color.as_rgba_f32();
+            writeln!(
+                out,
+                "        <viz:color r=\"{}\" g=\"{}\" b=\"{}\" a=\"{}\"/>",
+                (r * 255.0).round() as u8,
+                (g * 255.0).round() as u8,
+                (b * 255.0).round() as u8,
+                a
+            )?;
+        }
+        if let Some(position) = node.position {
+            writeln!(
+                out,
+                "        <viz:position x=\"{}\" y=\"{}\" z=\"{}\"/>",
+                position.x, position.y, position.z
+            )?;
+        }
+        if let Some(scale) = node.scale {
+            writeln!(out, "        <viz:size value=\"{}\"/>", scale)?;
+        }
+        writeln!(out, "      </node>")?;
+    }
+    writeln!(out, "    </nodes>")?;
+
+    writeln!(out, "    <edges>")?;
+    for (i, edge) in graph.edges.iter().enumerate() {
+        write!(
+            out,
+            "      <edge id=\"{}\" source=\"{}\" target=\"{}\" weight=\"{}\"",
+            i,
+            escape(&graph.nodes[edge.from].key),
+            escape(&graph.nodes[edge.to].key),
+            edge.weight
+        )?;
+        if let Some(label) = edge.attributes.get(LABEL_ATTRIBUTE) {
+            write!(out, " label=\"{}\"", escape(label))?;
+        }
+        writeln!(out, ">")?;
+        write_attvalues(&mut out, &edge_ids, &edge.attributes)?;
+        writeln!(out, "      </edge>")?;
+    }
+    writeln!(out, "    </edges>")?;
+    writeln!(out, "  </graph>")?;
+    writeln!(out, "</gexf>")?;
+    Ok(out)
+}
+
+/// Declares a string `<attribute>` per title and returns the ids assigned.
+fn declare_attributes<'a>(
+    out: &mut String,
+    class: &str,
+    titles: &BTreeSet<&'a str>,
+) -> Result<HashMap<&'a str, usize>, fmt::Error> {
+    let mut ids = HashMap::new();
+    if titles.is_empty() {
+        return Ok(ids);
+    }
+    writeln!(out, "    <attributes class=\"{class}\">")?;
+    for (id, title) in titles.iter().enumerate() {
+        writeln!(
+            out,
+            "      <attribute id=\"{id}\" title=\"{}\" type=\"string\"/>",
+            escape(title)
+        )?;
+        ids.insert(*title, id);
+    }
+    writeln!(out, "    </attributes>")?;
+    Ok(ids)
+}
+
+fn write_attvalues(
+    out: &mut String,
+    ids: &HashMap<&str, usize>,
+    attributes: &BTreeMap<String, String>,
+) -> fmt::Result {
+    let values: Vec<(usize, &String)> = attributes
+        .iter()
+        .filter_map(|(title, value)| ids.get(title.as_str()).map(|id| (*id, value)))
+        .collect();
+    if values.is_empty() {
+        return Ok(());
+    }
+    writeln!(out, "        <attvalues>")?;
+    for (id, value) in values {
+        writeln!(
+            out,
+            "          <attvalue for=\"{id}\" value=\"{}\"/>",
+            escape(value)
+        )?;
+    }
+    writeln!(out, "        </attvalues>")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{edge_list, sample_graph};
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let graph = sample_graph();
+        let read = read(&write(&graph).unwrap()).unwrap();
+        assert!(read.warnings.is_empty(), "{:?}", read.warnings);
+        assert_eq!(read.nodes.len(), graph.nodes.len());
+        for (read, written) in read.nodes.iter().zip(graph.nodes.iter()) {
+            assert_eq!(read.key, written.key);
+            assert_eq!(read.position, written.position);
+            assert_eq!(read.scale, written.scale);
+            assert_eq!(
+                read.color.map(|color| color.as_rgba_u8()),
+                written.color.map(|color| color.as_rgba_u8())
+            );
+            assert_eq!(read.attributes, written.attributes);
+        }
+        assert_eq!(edge_list(&read), edge_list(&graph));
+        for (read, written) in read.edges.iter().zip(graph.edges.iter()) {
+            assert_eq!(read.attributes, written.attributes);
+        }
+    }
+}
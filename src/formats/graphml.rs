@@ -0,0 +1,376 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::{self, Write};
+
+use bevy::math::Vec3;
+use quick_xml::escape::escape;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+
+use super::{xml_attribute, EdgeData, GraphData, ImportError};
+use crate::identifiers::ConnectionKind;
+
+/// Node `<data>` keys that carry the layout rather than free-form attributes.
+const POSITION_KEYS: [&str; 3] = ["x", "y", "z"];
+const SIZE_KEY: &str = "size";
+const WEIGHT_KEY: &str = "weight";
+
+struct KeyDef {
+    name: String,
+    domain: String,
+    default: Option<String>,
+}
+
+enum Element {
+    Node(usize),
+    Edge(EdgeData),
+    SkippedEdge,
+    Other,
+}
+
+pub fn read(text: &str) -> Result<GraphData, ImportError> {
+    let mut reader = Reader::from_str(text);
+    reader.trim_text(true);
+
+    let mut graph = GraphData::default();
+    let mut keys: HashMap<String, KeyDef> = HashMap::new();
+    let mut element = Element::Other;
+    // `<key>` whose `<default>` is being read
+    let mut key_id: Option<String> = None;
+    let mut in_default = false;
+    // `<data key=..>` being read and its text so far
+    let mut data: Option<(String, String)> = None;
+
+    loop {
+        let event = reader.read_event().map_err(|err| {
+            ImportError::Parse(format!(
+                "XML error at byte {}: {}",
+                reader.buffer_position(),
+                err
+            ))
+        })?;
+
+        match event {
+            Event::Start(ref e) | Event::Empty(ref e) => {
+                let empty = matches!(event, Event::Empty(_));
+                match e.local_name().as_ref() {
+                    b"key" => {
+                        let Some(id) = xml_attribute(e, b"id") else {
+                            graph.warn("<key> without id".to_string());
+                            continue;
+                        };
+                        keys.insert(
+                            id.clone(),
+                            KeyDef {
+                                name: xml_attribute(e, b"attr.name").unwrap_or_else(|| id.clone()),
+                                domain: xml_attribute(e, b"for")
+                                    .unwrap_or_else(|| "all".to_string()),
+                                default: None,
+                            },
+                        );
+                        if !empty {
+                            key_id = Some(id);
+                        }
+                    }
+                    b"default" if !empty => in_default = key_id.is_some(),
+                    b"node" => {
+                        let Some(id) = xml_attribute(e, b"id") else {
+                            graph.warn("<node> without id".to_string());
+                            continue;
+                        };
+                        let index = graph.node(&id);
+                        if empty {
+                            finish_node(&mut graph, &keys, index);
+                        } else {
+                            element = Element::Node(index);
+                        }
+                    }
+                    b"edge" => {
+                        let edge = match (xml_attribute(e, b"source"), xml_attribute(e, b"target"))
+                        {
+                            (Some(source), Some(target)) if source == target => {
+                                graph.node(&source);
+                                graph.warn(format!("skipping self-loop on {}", source));
+                                None
+                            }
+                            (Some(source), Some(target)) => Some(EdgeData {
+                                from: graph.node(&source),
+                                to: graph.node(&target),
+                                weight: 1.0,
+                                kind: ConnectionKind::Generic,
+                                attributes: BTreeMap::new(),
+                            }),
+                            _ => {
+                                graph.warn("<edge> without source or target".to_string());
+                                None
+                            }
+                        };
+                        match (edge, empty) {
+                            (Some(edge), true) => finish_edge(&mut graph, &keys, edge),
+                            (Some(edge), false) => element = Element::Edge(edge),
+                            (None, true) => {}
+                            (None, false) => element = Element::SkippedEdge,
+                        }
+                    }
+                    b"hyperedge" => graph.warn("skipping <hyperedge>".to_string()),
+                    b"data" if !empty => {
+                        data = xml_attribute(e, b"key").map(|key| (key, String::new()));
+                    }
+                    _ => {}
+                }
+            }
+            Event::Text(e) => {
+                let text = e
+                    .unescape()
+                    .map_err(|err| ImportError::Parse(err.to_string()))?;
+                append_text(&mut data, &mut keys, &key_id, in_default, &text);
+            }
+            Event::CData(e) => {
+                let text = String::from_utf8_lossy(&e).to_string();
+                append_text(&mut data, &mut keys, &key_id, in_default, &text);
+            }
+            Event::End(e) => match e.local_name().as_ref() {
+                b"key" => key_id = None,
+                b"default" => in_default = false,
+                b"data" => {
+                    if let Some((key, value)) = data.take() {
+                        let name = keys.get(&key).map_or(key, |key| key.name.clone());
+                        match &mut element {
+                            Element::Node(index) => {
+                                graph.nodes[*index].attributes.insert(name, value);
+                            }
+                            Element::Edge(edge) => {
+                                edge.attributes.insert(name, value);
+                            }
+                            Element::SkippedEdge | Element::Other => {}
+                        }
+                    }
+                }
+                b"node" => {
+                    if let Element::Node(index) = std::mem::replace(&mut element, Element::Other) {
+                        finish_node(&mut graph, &keys, index);
+                    }
+                }
+                b"edge" => {
+                    if let Element::Edge(edge) = std::mem::replace(&mut element, Element::Other) {
+                        finish_edge(&mut graph, &keys, edge);
+                    }
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    if graph.nodes.is_empty() {
+        return Err(ImportError::Parse("no nodes found".to_string()));
+    }
+
+    Ok(graph)
+}
+
+fn append_text(
+    data: &mut Option<(String, String)>,
+    keys: &mut HashMap<String, KeyDef>,
+    key_id: &Option<String>,
+    in_default: bool,
+    text: &str,
+) {
+    if let Some((_, value)) = data {
+        value.push_str(text);
+    } else if in_default {
+        if let Some(key) = key_id.as_ref().and_then(|id| keys.get_mut(id)) {
+            key.default.get_or_insert_with(String::new).push_str(text);
+        }
+    }
+}
+
+fn apply_defaults(
+    attributes: &mut BTreeMap<String, String>,
+    keys: &HashMap<String, KeyDef>,
+    domain: &str,
+) {
+    for key in keys.values() {
+        if key.domain != domain && key.domain != "all" {
+            continue;
+        }
+        if let Some(default) = &key.default {
+            attributes
+                .entry(key.name.clone())
+                .or_insert_with(|| default.clone());
+        }
+    }
+}
+
+fn finish_node(graph: &mut GraphData, keys: &HashMap<String, KeyDef>, index: usize) {
+    let mut attributes = std::mem::take(&mut graph.nodes[index].attributes);
+    apply_defaults(&mut attributes, keys, "node");
+
+    let mut coordinates = [None; 3];
+    for (coordinate, name) in coordinates.iter_mut().zip(POSITION_KEYS) {
+        if let Some(value) = attributes.remove(name) {
+            match value.trim().parse::<f32>() {
+                Ok(value) => *coordinate = Some(value),
+                Err(_) => graph.warn(format!(
+                    "node {}: invalid {} {:?}",
+                    graph.nodes[index].key, name, value
+                )),
+            }
+        }
+    }
+    if let [Some(x), Some(y), z] = coordinates {
+        graph.nodes[index].position = Some(Vec3::new(x, y, z.unwrap_or(0.0)));
+    }
+
+    if let Some(value) = attributes.remove(SIZE_KEY) {
+        match value.trim().parse::<f32>() {
+            Ok(size) if size > 0.0 => graph.nodes[index].scale = Some(size),
+            _ => graph.warn(format!(
+                "node {}: invalid size {:?}",
+                graph.nodes[index].key, value
+            )),
+        }
+    }
+
+    graph.nodes[index].attributes = attributes;
+}
+
+fn finish_edge(graph: &mut GraphData, keys: &HashMap<String, KeyDef>, mut edge: EdgeData) {
+    apply_defaults(&mut edge.attributes, keys, "edge");
+    if let Some(value) = edge.attributes.remove(WEIGHT_KEY) {
+        match value.trim().parse::<f32>() {
+            Ok(weight) if weight.is_finite() => edge.weight = weight,
+            _ => {
+                graph.warn(format!(
+                    "edge {} -> {}: invalid weight {:?}",
+                    graph.nodes[edge.from].key, graph.nodes[edge.to].key, value
+                ));
+                return;
+            }
+        }
+    }
+    graph.edges.push(edge);
+}
+
+pub fn write(graph: &GraphData) -> Result<String, fmt::Error> {
+    let node_names: BTreeSet<&str> = graph
+        .nodes
+        .iter()
+        .flat_map(|node| node.attributes.keys().map(String::as_str))
+        .filter(|name| !POSITION_KEYS.contains(name) && *name != SIZE_KEY)
+        .collect();
+    let edge_names: BTreeSet<&str> = graph
+        .edges
+        .iter()
+        .flat_map(|edge| edge.attributes.keys().map(String::as_str))
+        .filter(|name| *name != WEIGHT_KEY)
+        .collect();
+
+    let mut out = String::new();
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(
+        out,
+        "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">"
+    )?;
+    for name in POSITION_KEYS.iter().chain([&SIZE_KEY]) {
+        writeln!(
+            out,
+            "  <key id=\"{name}\" for=\"node\" attr.name=\"{name}\" attr.type=\"float\"/>"
+        )?;
+    }
+    writeln!(
+        out,
+        "  <key id=\"{WEIGHT_KEY}\" for=\"edge\" attr.name=\"{WEIGHT_KEY}\" attr.type=\"double\"/>"
+    )?;
+    let node_ids = declare_keys(&mut out, "node", &node_names)?;
+    let edge_ids = declare_keys(&mut out, "edge", &edge_names)?;
+
+    writeln!(out, "  <graph id=\"G\" edgedefault=\"directed\">")?;
+    for node in graph.nodes.iter() {
+        writeln!(out, "    <node id=\"{}\">", escape(&node.key))?;
+        if let Some(position) = node.position {
+            for (name, value) in POSITION_KEYS.iter().zip(position.to_array()) {
+                writeln!(out, "      <data key=\"{name}\">{value}</data>")?;
+            }
+        }
+        if let Some(scale) = node.scale {
+            writeln!(out, "      <data key=\"{SIZE_KEY}\">{scale}</data>")?;
+        }
+        write_data(&mut out, &node_ids, &node.attributes)?;
+        writeln!(out, "    </node>")?;
+    }
+    for edge in graph.edges.iter() {
+        writeln!(
+            out,
+            "    <edge source=\"{}\" target=\"{}\">",
+            escape(&graph.nodes[edge.from].key),
+            escape(&graph.nodes[edge.to].key)
+        )?;
+        writeln!(
+            out,
+            "      <data key=\"{WEIGHT_KEY}\">{}</data>",
+            edge.weight
+        )?;
+        write_data(&mut out, &edge_ids, &edge.attributes)?;
+        writeln!(out, "    </edge>")?;
+    }
+    writeln!(out, "  </graph>")?;
+    writeln!(out, "</graphml>")?;
+    Ok(out)
+}
+
+/// Declares a string `<key>` per attribute name and returns the ids assigned.
+fn declare_keys<'a>(
+    out: &mut String,
+    domain: &str,
+    names: &BTreeSet<&'a str>,
+) -> Result<HashMap<&'a str, String>, fmt::Error> {
+    let mut ids = HashMap::new();
+    for (i, name) in names.iter().enumerate() {
+        let id = format!("{domain}_{i}");
+        writeln!(
+            out,
+            "  <key id=\"{id}\" for=\"{domain}\" attr.name=\"{}\" attr.type=\"string\"/>",
+            escape(name)
+        )?;
+        ids.insert(*name, id);
+    }
+    Ok(ids)
+}
+
+fn write_data(
+    out: &mut String,
+    ids: &HashMap<&str, String>,
+    attributes: &BTreeMap<String, String>,
+) -> fmt::Result {
+    for (name, value) in attributes.iter() {
+        if let Some(id) = ids.get(name.as_str()) {
+            writeln!(out, "      <data key=\"{id}\">{}</data>", escape(value))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{edge_list, sample_graph};
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let graph = sample_graph();
+        let read = read(&write(&graph).unwrap()).unwrap();
+        assert!(read.warnings.is_empty(), "{:?}", read.warnings);
+        assert_eq!(read.nodes.len(), graph.nodes.len());
+        for (read, written) in read.nodes.iter().zip(graph.nodes.iter()) {
+            assert_eq!(read.key, written.key);
+            assert_eq!(read.position, written.position);
+            assert_eq!(read.scale, written.scale);
+            assert_eq!(read.attributes, written.attributes);
+        }
+        assert_eq!(edge_list(&read), edge_list(&graph));
+        for (read, written) in read.edges.iter().zip(graph.edges.iter()) {
+            assert_eq!(read.attributes, written.attributes);
+        }
+    }
+}
@@ -0,0 +1,549 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use quick_xml::events::BytesStart;
+
+use crate::assets::{ColorMaterials, MyAssets};
+use crate::cli::CliArgs;
+use crate::events::{DeleteIdentifiersEvent, ExportGraphEvent, ImportGraphEvent};
+use crate::graph_index::GraphIndex;
+use crate::identifiers::{
+    spawn_connection, spawn_identifier, Attributes, Connection, ConnectionKind, Identifier,
+    IdentifierMaterial, Selection, IDENTIFIER_SCALE,
+};
+use crate::resources::{Configuration, SeededRng};
+use crate::util::random_point_in_sphere;
+
+mod edge_list;
+mod gexf;
+mod graphml;
+mod veramo_json;
+#[cfg(not(target_arch = "wasm32"))]
+mod veramo_sqlite;
+
+/// Edge attribute that carries `ConnectionKind` through generic formats.
+const KIND_ATTRIBUTE: &str = "kind";
+/// Node attribute that becomes `Identifier::label`.
+const LABEL_ATTRIBUTE: &str = "label";
+
+/// File formats a graph can be read from and written to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+pub enum GraphFormat {
+    Csv,
+    Tsv,
+    Graphml,
+    Gexf,
+    VeramoJson,
+    /// Veramo data-store database. Not available in the web build.
+    VeramoSqlite,
+}
+
+impl GraphFormat {
+    pub const ALL: [GraphFormat; 6] = [
+        GraphFormat::Csv,
+        GraphFormat::Tsv,
+        GraphFormat::Graphml,
+        GraphFormat::Gexf,
+        GraphFormat::VeramoJson,
+        GraphFormat::VeramoSqlite,
+    ];
+
+    /// Guesses the format from the file extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "csv" => Some(GraphFormat::Csv),
+            "tsv" | "tab" => Some(GraphFormat::Tsv),
+            "graphml" => Some(GraphFormat::Graphml),
+            "gexf" => Some(GraphFormat::Gexf),
+            "json" => Some(GraphFormat::VeramoJson),
+            "sqlite" | "sqlite3" | "db" => Some(GraphFormat::VeramoSqlite),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GraphFormat::Csv => "CSV edge list",
+            GraphFormat::Tsv => "TSV edge list",
+            GraphFormat::Graphml => "GraphML",
+            GraphFormat::Gexf => "GEXF",
+            GraphFormat::VeramoJson => "Veramo JSON",
+            GraphFormat::VeramoSqlite => "Veramo SQLite",
+        }
+    }
+
+    /// Whether graphs can be exported in this format, not only imported.
+    pub fn is_writable(&self) -> bool {
+        !matches!(self, GraphFormat::VeramoJson | GraphFormat::VeramoSqlite)
+    }
+}
+
+#[derive(Debug)]
+pub enum ImportError {
+    Io(std::io::Error),
+    UnknownFormat(PathBuf),
+    Parse(String),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Io(err) => write!(f, "{}", err),
+            ImportError::UnknownFormat(path) => {
+                write!(f, "cannot tell the format of {}", path.display())
+            }
+            ImportError::Parse(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<std::io::Error> for ImportError {
+    fn from(err: std::io::Error) -> Self {
+        ImportError::Io(err)
+    }
+}
+
+#[derive(Default)]
+pub struct NodeData {
+    pub key: String,
+    pub attributes: BTreeMap<String, String>,
+    /// Stored layout, if the file has one. Missing positions are randomised on import.
+    pub position: Option<Vec3>,
+    pub scale: Option<f32>,
+    pub color: Option<Color>,
+}
+
+pub struct EdgeData {
+    pub from: usize,
+    pub to: usize,
+    pub weight: f32,
+    pub kind: ConnectionKind,
+    pub attributes: BTreeMap<String, String>,
+}
+
+/// Format independent graph that importers produce and the spawner consumes.
+/// Edges refer to nodes by their position in `nodes`.
+#[derive(Default)]
+pub struct GraphData {
+    pub nodes: Vec<NodeData>,
+    pub edges: Vec<EdgeData>,
+    /// Problems with individual records that were skipped while reading.
+    pub warnings: Vec<String>,
+    node_indices: HashMap<String, usize>,
+}
+
+impl GraphData {
+    /// Returns the index of the node with `key`, adding it if it is new.
+    pub fn node(&mut self, key: &str) -> usize {
+        if let Some(&index) = self.node_indices.get(key) {
+            return index;
+        }
+        let index = self.nodes.len();
+        self.nodes.push(NodeData {
+            key: key.to_string(),
+            ..Default::default()
+        });
+        self.node_indices.insert(key.to_string(), index);
+        index
+    }
+
+    /// Like `node`, for a DID: its method becomes the `method` attribute.
+    pub fn did_node(&mut self, did: &str) -> usize {
+        let index = self.node(did);
+        if let Some(method) = did
+            .strip_prefix("did:")
+            .and_then(|rest| rest.split(':').next())
+        {
+            self.nodes[index]
+                .attributes
+                .insert("method".to_string(), method.to_string());
+        }
+        index
+    }
+
+    pub fn warn(&mut self, message: String) {
+        self.warnings.push(message);
+    }
+}
+
+pub fn read_graph(path: &Path, format: GraphFormat) -> Result<GraphData, ImportError> {
+    match format {
+        GraphFormat::Csv => edge_list::read(&std::fs::read(path)?, b','),
+        GraphFormat::Tsv => edge_list::read(&std::fs::read(path)?, b'\t'),
+        GraphFormat::Graphml => graphml::read(as_text(&std::fs::read(path)?)?),
+        GraphFormat::Gexf => gexf::read(as_text(&std::fs::read(path)?)?),
+        GraphFormat::VeramoJson => veramo_json::read(as_text(&std::fs::read(path)?)?),
+        GraphFormat::VeramoSqlite => read_sqlite(path),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_sqlite(path: &Path) -> Result<GraphData, ImportError> {
+    veramo_sqlite::read(path)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read_sqlite(_path: &Path) -> Result<GraphData, ImportError> {
+    Err(ImportError::Parse(
+        "SQLite databases can only be read by the desktop build".to_string(),
+    ))
+}
+
+pub fn write_graph(path: &Path, format: GraphFormat, graph: &GraphData) -> std::io::Result<()> {
+    let contents = match format {
+        GraphFormat::Csv => edge_list::write(graph, b',').map_err(std::io::Error::other)?,
+        GraphFormat::Tsv => edge_list::write(graph, b'\t').map_err(std::io::Error::other)?,
+        GraphFormat::Graphml => graphml::write(graph).map_err(std::io::Error::other)?,
+        GraphFormat::Gexf => gexf::write(graph).map_err(std::io::Error::other)?,
+        GraphFormat::VeramoJson | GraphFormat::VeramoSqlite => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                format!("{} can only be imported", format.name()),
+            ))
+        }
+    };
+    std::fs::write(path, contents)
+}
+
+fn as_text(contents: &[u8]) -> Result<&str, ImportError> {
+    std::str::from_utf8(contents).map_err(|err| ImportError::Parse(err.to_string()))
+}
+
+/// Unescaped value of the XML attribute `name` on `e`.
+fn xml_attribute(e: &BytesStart, name: &[u8]) -> Option<String> {
+    e.attributes()
+        .flatten()
+        .find(|attr| attr.key.as_ref() == name)
+        .and_then(|attr| attr.unescape_value().ok())
+        .map(|value| value.to_string())
+}
+
+/// Outcome of the last import, shown in the Import window.
+#[derive(Resource, Default)]
+pub struct ImportStatus {
+    pub message: Option<String>,
+    pub warnings: Vec<String>,
+}
+
+/// Outcome of the last export, shown in the Export window.
+#[derive(Resource, Default)]
+pub struct ExportStatus {
+    pub message: Option<String>,
+}
+
+pub struct FormatsPlugin;
+
+impl Plugin for FormatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ImportStatus>()
+            .init_resource::<ExportStatus>()
+            .add_systems(Startup, import_from_command_line)
+            .add_systems(Update, import_graph)
+            .add_systems(Update, export_graph);
+    }
+}
+
+fn import_from_command_line(args: Res<CliArgs>, mut ev: EventWriter<ImportGraphEvent>) {
+    if let Some(path) = &args.path {
+        ev.send(ImportGraphEvent {
+            path: path.clone(),
+            format: None,
+            replace: true,
+        });
+    }
+}
+
+fn import_graph(
+    mut commands: Commands,
+    mut ev: EventReader<ImportGraphEvent>,
+    mut ev_delete: EventWriter<DeleteIdentifiersEvent>,
+    mut status: ResMut<ImportStatus>,
+    configuration: Res<Configuration>,
+    mut seeded_rng: ResMut<SeededRng>,
+    my_assets: Res<MyAssets>,
+    mut color_materials: ResMut<ColorMaterials>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    graph_index: Res<GraphIndex>,
+    existing_query: Query<Entity, With<Identifier>>,
+    transform_query: Query<&Transform, With<Identifier>>,
+) {
+    for e in ev.read() {
+        let result = e
+            .format
+            .or_else(|| GraphFormat::from_path(&e.path))
+            .ok_or_else(|| ImportError::UnknownFormat(e.path.clone()))
+            .and_then(|format| read_graph(&e.path, format));
+
+        let graph = match result {
+            Ok(graph) => graph,
+            Err(err) => {
+                error!("Failed to import {}: {}", e.path.display(), err);
+                status.message = Some(format!("Failed to import {}: {}", e.path.display(), err));
+                status.warnings.clear();
+                continue;
+            }
+        };
+
+        for warning in graph.warnings.iter() {
+            warn!("{}: {}", e.path.display(), warning);
+        }
+
+        if e.replace {
+            // Their connections go with them, and all of it can be undone.
+            ev_delete.send(DeleteIdentifiersEvent(existing_query.iter().collect()));
+        }
+
+        // When merging, identifiers whose key is already in the world are reused.
+        let existing: Vec<Option<(Entity, Vec3)>> = graph
+            .nodes
+            .iter()
+            .map(|node| {
+                if e.replace {
+                    return None;
+                }
+                let entity = graph_index.entity_of_key(&node.key)?;
+                let transform = transform_query.get(entity).ok()?;
+                Some((entity, transform.translation))
+            })
+            .collect();
+
+        let positions: Vec<Vec3> = graph
+            .nodes
+            .iter()
+            .zip(existing.iter())
+            .map(|(node, existing)| match existing {
+                Some((_, position)) => *position,
+                None => node.position.unwrap_or_else(|| {
+                    let (x, y, z) =
+                        random_point_in_sphere(seeded_rng.rng(), configuration.container_size);
+                    Vec3::new(x, y, z)
+                }),
+            })
+            .collect();
+
+        let entities: Vec<Entity> = graph
+            .nodes
+            .iter()
+            .zip(positions.iter())
+            .zip(existing.iter())
+            .map(|((node, &position), existing)| {
+                if let Some((entity, _)) = existing {
+                    return *entity;
+                }
+                let entity = spawn_identifier(
+                    &mut commands,
+                    &my_assets,
+                    &configuration,
+                    Identifier {
+                        key: node.key.clone(),
+                        label: node.attributes.get(LABEL_ATTRIBUTE).cloned(),
+                    },
+                    position,
+                    node.scale.unwrap_or(IDENTIFIER_SCALE),
+                );
+                if !node.attributes.is_empty() {
+                    commands
+                        .entity(entity)
+                        .insert(Attributes(node.attributes.clone()));
+                }
+                if let Some(color) = node.color {
+                    let material = color_materials.get_or_add(color, &mut materials);
+                    commands
+                        .entity(entity)
+                        .insert((material.clone(), IdentifierMaterial(material)));
+                }
+                entity
+            })
+            .collect();
+
+        for edge in graph.edges.iter() {
+            let mut attributes = edge.attributes.clone();
+            let kind = match attributes
+                .get(KIND_ATTRIBUTE)
+                .and_then(|k| ConnectionKind::from_name(k))
+            {
+                Some(kind) if edge.kind == ConnectionKind::Generic => {
+                    attributes.remove(KIND_ATTRIBUTE);
+                    kind
+                }
+                _ => edge.kind,
+            };
+            let entity = spawn_connection(
+                &mut commands,
+                &my_assets,
+                &configuration,
+                Connection {
+                    from: entities[edge.from],
+                    to: entities[edge.to],
+                    weight: edge.weight,
+                    kind,
+                },
+                positions[edge.from],
+                positions[edge.to],
+            );
+            if !attributes.is_empty() {
+                commands.entity(entity).insert(Attributes(attributes));
+            }
+        }
+
+        info!(
+            "Imported {} identifiers and {} connections from {}",
+            graph.nodes.len(),
+            graph.edges.len(),
+            e.path.display()
+        );
+        status.message = Some(format!(
+            "Imported {} identifiers and {} connections",
+            graph.nodes.len(),
+            graph.edges.len()
+        ));
+        status.warnings = graph.warnings;
+    }
+}
+
+fn export_graph(
+    mut ev: EventReader<ExportGraphEvent>,
+    mut status: ResMut<ExportStatus>,
+    my_assets: Res<MyAssets>,
+    materials: Res<Assets<StandardMaterial>>,
+    graph_index: Res<GraphIndex>,
+    selection: Res<Selection>,
+    identifier_query: Query<(
+        &Identifier,
+        &Transform,
+        Option<&Attributes>,
+        Option<&IdentifierMaterial>,
+    )>,
+    connection_query: Query<(&Connection, Option<&Attributes>)>,
+) {
+    for e in ev.read() {
+        let mut graph = GraphData::default();
+        // Graph index -> node in `graph`
+        let mut nodes: Vec<Option<usize>> = vec![None; graph_index.len()];
+        for (i, node_index) in nodes.iter_mut().enumerate() {
+            let entity = graph_index.entity(i);
+            if e.selected_only && !selection.0.contains(&entity) {
+                continue;
+            }
+            let Ok((identifier, transform, attributes, material)) = identifier_query.get(entity)
+            else {
+                continue;
+            };
+            let index = graph.node(&identifier.key);
+            let node = &mut graph.nodes[index];
+            node.position = Some(transform.translation);
+            node.scale = Some(transform.scale.x);
+            node.color = materials
+                .get(material.map_or(&my_assets.identifier_material_handle, |m| &m.0))
+                .map(|material| material.base_color);
+            if let Some(attributes) = attributes {
+                node.attributes = attributes.0.clone();
+            }
+            *node_index = Some(index);
+        }
+        for (connection, attributes) in connection_query.iter() {
+            let node = |entity| graph_index.index_of(entity).and_then(|i| nodes[i]);
+            let (Some(from), Some(to)) = (node(connection.from), node(connection.to)) else {
+                continue;
+            };
+            let mut attributes = attributes.map(|a| a.0.clone()).unwrap_or_default();
+            if connection.kind != ConnectionKind::Generic {
+                attributes.insert(
+                    KIND_ATTRIBUTE.to_string(),
+                    connection.kind.name().to_string(),
+                );
+            }
+            graph.edges.push(EdgeData {
+                from,
+                to,
+                weight: connection.weight,
+                kind: connection.kind,
+                attributes,
+            });
+        }
+
+        match write_graph(&e.path, e.format, &graph) {
+            Ok(()) => {
+                info!(
+                    "Exported {} identifiers and {} connections to {}",
+                    graph.nodes.len(),
+                    graph.edges.len(),
+                    e.path.display()
+                );
+                status.message = Some(format!(
+                    "Exported {} identifiers and {} connections",
+                    graph.nodes.len(),
+                    graph.edges.len()
+                ));
+            }
+            Err(err) => {
+                error!("Failed to export {}: {}", e.path.display(), err);
+                status.message = Some(format!("Failed to export {}: {}", e.path.display(), err));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Three identifiers, one with a layout, colour and attributes and one whose
+    /// key needs quoting, and two weighted connections.
+    pub fn sample_graph() -> GraphData {
+        let mut graph = GraphData::default();
+        let alice = graph.did_node("did:example:alice");
+        let bob = graph.did_node("did:example:bob");
+        let carol = graph.node("carol, \"<the third>\"");
+        graph.nodes[alice]
+            .attributes
+            .insert(LABEL_ATTRIBUTE.to_string(), "Alice & co".to_string());
+        graph.nodes[alice].position = Some(Vec3::new(1.5, -2.0, 0.25));
+        graph.nodes[alice].scale = Some(0.75);
+        graph.nodes[alice].color = Some(Color::rgb_u8(255, 128, 0));
+        graph.nodes[bob].position = Some(Vec3::new(0.0, 1.0, -3.0));
+        graph.edges.push(EdgeData {
+            from: alice,
+            to: bob,
+            weight: 2.5,
+            kind: ConnectionKind::Generic,
+            attributes: BTreeMap::from([("type".to_string(), "VerifiableCredential".to_string())]),
+        });
+        graph.edges.push(EdgeData {
+            from: bob,
+            to: carol,
+            weight: 1.0,
+            kind: ConnectionKind::Generic,
+            attributes: BTreeMap::new(),
+        });
+        graph
+    }
+
+    /// Edges as `(from key, to key, weight)`.
+    pub fn edge_list(graph: &GraphData) -> Vec<(&str, &str, f32)> {
+        graph
+            .edges
+            .iter()
+            .map(|edge| {
+                (
+                    graph.nodes[edge.from].key.as_str(),
+                    graph.nodes[edge.to].key.as_str(),
+                    edge.weight,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn did_node_sets_the_method() {
+        let mut graph = GraphData::default();
+        let index = graph.did_node("did:web:example.com");
+        assert_eq!(graph.did_node("did:web:example.com"), index);
+        assert_eq!(graph.nodes[index].attributes["method"], "web");
+        let index = graph.did_node("not-a-did");
+        assert!(graph.nodes[index].attributes.is_empty());
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use super::{EdgeData, GraphData, ImportError};
+use crate::identifiers::ConnectionKind;
+
+/// Reads a JSON dump of DIDs and W3C Verifiable Credentials, as returned by
+/// Veramo's `didManagerFind` and `dataStoreORMGetVerifiableCredentials`:
+///
+/// ```json
+/// {
+///   "identifiers": [{ "did": "did:ethr:0x..", "alias": "..", "provider": ".." }],
+///   "credentials": [{ "hash": "..", "verifiableCredential": { "issuer": .., "credentialSubject": .. } }]
+/// }
+/// ```
+///
+/// A bare array of credentials is accepted too. Every DID becomes a node and
+/// every credential a directed edge from its issuer to each subject.
+pub fn read(text: &str) -> Result<GraphData, ImportError> {
+    let root: Value =
+        serde_json::from_str(text).map_err(|err| ImportError::Parse(err.to_string()))?;
+
+    let (identifiers, credentials) = match &root {
+        Value::Array(credentials) => (None, Some(credentials)),
+        Value::Object(object) => (
+            object.get("identifiers").and_then(Value::as_array),
+            object.get("credentials").and_then(Value::as_array),
+        ),
+        _ => {
+            return Err(ImportError::Parse(
+                "expected an object or an array of credentials".to_string(),
+            ))
+        }
+    };
+
+    let mut graph = GraphData::default();
+
+    for (i, identifier) in identifiers.into_iter().flatten().enumerate() {
+        let Some(did) = identifier.get("did").and_then(Value::as_str) else {
+            graph.warn(format!("identifier {}: missing did", i));
+            continue;
+        };
+        let index = graph.did_node(did);
+        let attributes = &mut graph.nodes[index].attributes;
+        for field in ["alias", "provider", "controllerKeyId"] {
+            if let Some(value) = identifier.get(field).and_then(Value::as_str) {
+                attributes.insert(field.to_string(), value.to_string());
+            }
+        }
+    }
+
+    for (i, item) in credentials.into_iter().flatten().enumerate() {
+        let credential = item.get("verifiableCredential").unwrap_or(item);
+        if credential.is_string() {
+            graph.warn(format!("credential {}: JWT strings are not decoded", i));
+            continue;
+        }
+
+        let Some(issuer) = credential.get("issuer").and_then(id_of) else {
+            graph.warn(format!("credential {}: missing issuer", i));
+            continue;
+        };
+        let subjects: Vec<&str> = match credential.get("credentialSubject") {
+            Some(Value::Array(subjects)) => subjects.iter().filter_map(id_of).collect(),
+            Some(subject) => id_of(subject).into_iter().collect(),
+            None => vec![],
+        };
+        if subjects.is_empty() {
+            graph.warn(format!("credential {}: no subject with an id", i));
+            continue;
+        }
+
+        let mut attributes = BTreeMap::new();
+        let types: Vec<&str> = match credential.get("type") {
+            Some(Value::Array(types)) => types.iter().filter_map(Value::as_str).collect(),
+            Some(Value::String(t)) => vec![t.as_str()],
+            _ => vec![],
+        };
+        let specific: Vec<&str> = types
+            .iter()
+            .copied()
+            .filter(|t| *t != "VerifiableCredential")
+            .collect();
+        let types = if specific.is_empty() { types } else { specific }.join(", ");
+        if !types.is_empty() {
+            attributes.insert("type".to_string(), types);
+        }
+        for field in ["id", "issuanceDate", "expirationDate"] {
+            if let Some(value) = credential.get(field).and_then(Value::as_str) {
+                attributes.insert(field.to_string(), value.to_string());
+            }
+        }
+        if let Some(hash) = item.get("hash").and_then(Value::as_str) {
+            attributes.insert("hash".to_string(), hash.to_string());
+        }
+
+        let from = graph.did_node(issuer);
+        for subject in subjects {
+            let to = graph.did_node(subject);
+            if from == to {
+                graph.warn(format!("credential {}: skipping self-issued credential", i));
+                continue;
+            }
+            graph.edges.push(EdgeData {
+                from,
+                to,
+                weight: 1.0,
+                kind: ConnectionKind::Credential,
+                attributes: attributes.clone(),
+            });
+        }
+    }
+
+    if graph.nodes.is_empty() {
+        return Err(ImportError::Parse(
+            "no identifiers or credentials found".to_string(),
+        ));
+    }
+
+    Ok(graph)
+}
+
+/// `"did:..."` or `{ "id": "did:..." }`
+fn id_of(value: &Value) -> Option<&str> {
+    match value {
+        Value::String(id) => Some(id),
+        Value::Object(object) => object.get("id").and_then(Value::as_str),
+        _ => None,
+    }
+}
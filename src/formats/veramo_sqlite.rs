@@ -0,0 +1,243 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use rusqlite::types::ValueRef;
+use rusqlite::{OpenFlags, Row};
+
+use super::{EdgeData, GraphData, ImportError};
+use crate::identifiers::ConnectionKind;
+
+/// Reads the `identifier`, `credential`, `presentation` and `message` tables of
+/// a Veramo data-store SQLite database. The file is opened read-only, so it is
+/// safe to point this at the database of a running agent. Missing tables are
+/// reported and skipped.
+pub fn read(path: &Path) -> Result<GraphData, ImportError> {
+    let db = rusqlite::Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .map_err(|err| ImportError::Parse(err.to_string()))?;
+
+    let mut graph = GraphData::default();
+
+    read_table(
+        &db,
+        &mut graph,
+        "identifier",
+        "SELECT did, alias, provider, controllerKeyId, saveDate FROM identifier",
+        |graph, row| {
+            let did: String = row.get(0)?;
+            let index = graph.did_node(&did);
+            let attributes = &mut graph.nodes[index].attributes;
+            for (i, field) in ["alias", "provider", "controllerKeyId", "saveDate"]
+                .iter()
+                .enumerate()
+            {
+                insert_optional(attributes, row, i + 1, field)?;
+            }
+            Ok(())
+        },
+    );
+
+    read_table(
+        &db,
+        &mut graph,
+        "credential",
+        "SELECT issuerDid, subjectDid, type, issuanceDate, expirationDate, id, hash FROM credential",
+        |graph, row| {
+            let issuer: String = row.get(0)?;
+            let Some(subject) = row.get::<_, Option<String>>(1)? else {
+                let hash: String = row.get(6)?;
+                graph.warn(format!("credential {}: no subject", hash));
+                graph.did_node(&issuer);
+                return Ok(());
+            };
+            let mut attributes = BTreeMap::new();
+            if let Some(types) = row.get::<_, Option<String>>(2)? {
+                attributes.insert("type".to_string(), credential_type(&types));
+            }
+            insert_optional(&mut attributes, row, 3, "issuanceDate")?;
+            insert_optional(&mut attributes, row, 4, "expirationDate")?;
+            insert_optional(&mut attributes, row, 5, "id")?;
+            insert_optional(&mut attributes, row, 6, "hash")?;
+            let hash: String = row.get(6)?;
+            add_edge(
+                graph,
+                &format!("credential {}", hash),
+                &issuer,
+                &subject,
+                ConnectionKind::Credential,
+                attributes,
+            );
+            Ok(())
+        },
+    );
+
+    read_table(
+        &db,
+        &mut graph,
+        "presentation",
+        "SELECT p.holderDid, v.identifierDid, p.type, p.issuanceDate, p.id, p.hash \
+         FROM presentation p \
+         LEFT JOIN presentation_verifier_identifier v ON v.presentationHash = p.hash",
+        |graph, row| {
+            let holder: String = row.get(0)?;
+            let Some(verifier) = row.get::<_, Option<String>>(1)? else {
+                graph.did_node(&holder);
+                return Ok(());
+            };
+            let mut attributes = BTreeMap::new();
+            if let Some(types) = row.get::<_, Option<String>>(2)? {
+                attributes.insert("type".to_string(), credential_type(&types));
+            }
+            insert_optional(&mut attributes, row, 3, "issuanceDate")?;
+            insert_optional(&mut attributes, row, 4, "id")?;
+            insert_optional(&mut attributes, row, 5, "hash")?;
+            let hash: String = row.get(5)?;
+            add_edge(
+                graph,
+                &format!("presentation {}", hash),
+                &holder,
+                &verifier,
+                ConnectionKind::Presentation,
+                attributes,
+            );
+            Ok(())
+        },
+    );
+
+    read_table(
+        &db,
+        &mut graph,
+        "message",
+        "SELECT fromDid, toDid, type, createdAt, threadId, id FROM message",
+        |graph, row| {
+            let (Some(from), Some(to)) = (
+                row.get::<_, Option<String>>(0)?,
+                row.get::<_, Option<String>>(1)?,
+            ) else {
+                return Ok(());
+            };
+            let mut attributes = BTreeMap::new();
+            insert_optional(&mut attributes, row, 2, "type")?;
+            insert_optional(&mut attributes, row, 3, "createdAt")?;
+            insert_optional(&mut attributes, row, 4, "threadId")?;
+            insert_optional(&mut attributes, row, 5, "id")?;
+            let id: Option<String> = row.get(5)?;
+            add_edge(
+                graph,
+                &format!("message {}", id.as_deref().unwrap_or("without id")),
+                &from,
+                &to,
+                ConnectionKind::Message,
+                attributes,
+            );
+            Ok(())
+        },
+    );
+
+    if graph.nodes.is_empty() {
+        return Err(ImportError::Parse(
+            "no identifiers found in the database".to_string(),
+        ));
+    }
+
+    Ok(graph)
+}
+
+fn read_table(
+    db: &rusqlite::Connection,
+    graph: &mut GraphData,
+    table: &str,
+    sql: &str,
+    mut read_row: impl FnMut(&mut GraphData, &Row) -> rusqlite::Result<()>,
+) {
+    let mut statement = match db.prepare(sql) {
+        Ok(statement) => statement,
+        Err(err) => {
+            graph.warn(format!("skipping table {}: {}", table, err));
+            return;
+        }
+    };
+    let mut rows = match statement.query([]) {
+        Ok(rows) => rows,
+        Err(err) => {
+            graph.warn(format!("skipping table {}: {}", table, err));
+            return;
+        }
+    };
+    let mut i = 0;
+    loop {
+        match rows.next() {
+            Ok(Some(row)) => {
+                if let Err(err) = read_row(graph, row) {
+                    graph.warn(format!("{} row {}: {}", table, i + 1, err));
+                }
+            }
+            Ok(None) => break,
+            Err(err) => {
+                graph.warn(format!("{} row {}: {}", table, i + 1, err));
+                break;
+            }
+        }
+        i += 1;
+    }
+}
+
+fn insert_optional(
+    attributes: &mut BTreeMap<String, String>,
+    row: &Row,
+    column: usize,
+    name: &str,
+) -> rusqlite::Result<()> {
+    if let Some(value) = text(row, column)? {
+        attributes.insert(name.to_string(), value);
+    }
+    Ok(())
+}
+
+/// Column value as text. SQLite may store dates as numbers depending on how
+/// the row was written, so anything but `NULL` and blobs is converted.
+fn text(row: &Row, column: usize) -> rusqlite::Result<Option<String>> {
+    Ok(match row.get_ref(column)? {
+        ValueRef::Null | ValueRef::Blob(_) => None,
+        ValueRef::Integer(value) => Some(value.to_string()),
+        ValueRef::Real(value) => Some(value.to_string()),
+        ValueRef::Text(value) => Some(String::from_utf8_lossy(value).to_string()),
+    })
+}
+
+/// Types are stored as a TypeORM `simple-array`, e.g. `VerifiableCredential,ProfileCredential`.
+fn credential_type(types: &str) -> String {
+    let types: Vec<&str> = types.split(',').map(str::trim).collect();
+    let specific: Vec<&str> = types
+        .iter()
+        .copied()
+        .filter(|t| *t != "VerifiableCredential" && *t != "VerifiablePresentation")
+        .collect();
+    if specific.is_empty() { types } else { specific }.join(", ")
+}
+
+/// Connects two DIDs. `record` names the row in warnings.
+fn add_edge(
+    graph: &mut GraphData,
+    record: &str,
+    from: &str,
+    to: &str,
+    kind: ConnectionKind,
+    attributes: BTreeMap<String, String>,
+) {
+    let from_index = graph.did_node(from);
+    let to_index = graph.did_node(to);
+    if from_index == to_index {
+        graph.warn(format!("{}: skipping self-issued {}", record, kind.name()));
+        return;
+    }
+    graph.edges.push(EdgeData {
+        from: from_index,
+        to: to_index,
+        weight: 1.0,
+        kind,
+        attributes,
+    });
+}
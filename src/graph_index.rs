@@ -0,0 +1,152 @@
+use bevy::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::identifiers::{Connection, Identifier};
+
+/// Dense numbering of the identifiers currently in the world, for graph algorithms.
+///
+/// Every identifier gets an index in `0..len()`, independent of its `Entity`
+/// id and generation, and can be looked up by entity or by key. Indices are
+/// kept dense: when an identifier is removed the last one takes its place, so
+/// they are only stable until the next removal and must not be stored.
+#[derive(Resource, Default)]
+pub struct GraphIndex {
+    entities: Vec<Entity>,
+    keys: Vec<String>,
+    by_entity: HashMap<Entity, usize>,
+    /// Indices of the identifiers with each key, oldest first. Keys repeat
+    /// while a replaced graph is still being despawned.
+    by_key: HashMap<String, Vec<usize>>,
+    /// Connection entity -> (from, to) identifier entities.
+    connections: BTreeMap<Entity, (Entity, Entity)>,
+}
+
+impl GraphIndex {
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn entity(&self, index: usize) -> Entity {
+        self.entities[index]
+    }
+
+    pub fn index_of(&self, entity: Entity) -> Option<usize> {
+        self.by_entity.get(&entity).copied()
+    }
+
+    /// The newest identifier with `key`.
+    pub fn entity_of_key(&self, key: &str) -> Option<Entity> {
+        self.by_key
+            .get(key)
+            .and_then(|indices| indices.last())
+            .map(|&index| self.entities[index])
+    }
+
+    /// Connections as `(from, to)` index pairs. Connections whose ends are not
+    /// both indexed are left out.
+    pub fn edges(&self) -> Vec<(usize, usize)> {
+        self.connections
+            .values()
+            .filter_map(|(from, to)| Some((self.index_of(*from)?, self.index_of(*to)?)))
+            .collect()
+    }
+
+    fn insert_identifier(&mut self, entity: Entity, key: &str) {
+        if self.by_entity.contains_key(&entity) {
+            return;
+        }
+        let index = self.entities.len();
+        self.entities.push(entity);
+        self.keys.push(key.to_string());
+        self.by_entity.insert(entity, index);
+        self.by_key.entry(key.to_string()).or_default().push(index);
+    }
+
+    fn remove_identifier(&mut self, entity: Entity) {
+        let Some(index) = self.by_entity.remove(&entity) else {
+            return;
+        };
+        let key = self.keys.swap_remove(index);
+        self.entities.swap_remove(index);
+        if let Some(indices) = self.by_key.get_mut(&key) {
+            indices.retain(|&i| i != index);
+            if indices.is_empty() {
+                self.by_key.remove(&key);
+            }
+        }
+
+        // The last identifier moved into the freed slot.
+        if index < self.entities.len() {
+            let moved = self.entities[index];
+            let old_index = self.entities.len();
+            self.by_entity.insert(moved, index);
+            if let Some(indices) = self.by_key.get_mut(&self.keys[index]) {
+                for i in indices.iter_mut().filter(|i| **i == old_index) {
+                    *i = index;
+                }
+            }
+        }
+    }
+}
+
+pub struct GraphIndexPlugin;
+
+impl Plugin for GraphIndexPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GraphIndex>()
+            .add_systems(PostUpdate, update_graph_index);
+    }
+}
+
+/// Runs after `Update`, so identifiers spawned or despawned by any system this
+/// frame are indexed before algorithms run in the next one.
+fn update_graph_index(
+    mut index: ResMut<GraphIndex>,
+    mut removed_identifiers: RemovedComponents<Identifier>,
+    mut removed_connections: RemovedComponents<Connection>,
+    added_identifiers: Query<(Entity, &Identifier), Added<Identifier>>,
+    added_connections: Query<(Entity, &Connection), Added<Connection>>,
+) {
+    for entity in removed_identifiers.read() {
+        index.remove_identifier(entity);
+    }
+    for entity in removed_connections.read() {
+        index.connections.remove(&entity);
+    }
+    for (entity, identifier) in added_identifiers.iter() {
+        index.insert_identifier(entity, &identifier.key);
+    }
+    for (entity, connection) in added_connections.iter() {
+        index
+            .connections
+            .insert(entity, (connection.from, connection.to));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_moves_to_the_identifier_left_with_it() {
+        let (old, new, other) = (
+            Entity::from_raw(1),
+            Entity::from_raw(2),
+            Entity::from_raw(3),
+        );
+        let mut index = GraphIndex::default();
+        index.insert_identifier(old, "did:example:a");
+        index.insert_identifier(other, "did:example:b");
+        index.insert_identifier(new, "did:example:a");
+        assert_eq!(index.entity_of_key("did:example:a"), Some(new));
+
+        index.remove_identifier(old);
+        assert_eq!(index.entity_of_key("did:example:a"), Some(new));
+        assert_eq!(index.entity_of_key("did:example:b"), Some(other));
+        assert_eq!(index.index_of(new), Some(0));
+
+        index.remove_identifier(new);
+        assert_eq!(index.entity_of_key("did:example:a"), None);
+        assert_eq!(index.len(), 1);
+    }
+}
@@ -0,0 +1,573 @@
+use bevy::prelude::*;
+use bevy_easings::{EasingChainComponent, EasingComponent};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::assets::MyAssets;
+use crate::events::{DeleteIdentifiersEvent, DeselectIdentifierEvent, RedoEvent, UndoEvent};
+use crate::identifiers::{
+    spawn_connection, spawn_identifier, Attributes, Connection, Hidden, Identifier,
+    IdentifierMaterial, Pinned,
+};
+use crate::resources::Configuration;
+
+/// Most edits kept for undo.
+const MAX_HISTORY: usize = 100;
+
+/// Everything needed to spawn an identifier again after it was despawned.
+#[derive(Clone)]
+pub struct IdentifierRecord {
+    entity: Entity,
+    identifier: Identifier,
+    transform: Transform,
+    attributes: Option<Attributes>,
+    material: Option<IdentifierMaterial>,
+    pinned: bool,
+    hidden: bool,
+}
+
+#[derive(Clone)]
+pub struct ConnectionRecord {
+    entity: Entity,
+    connection: Connection,
+    attributes: Option<Attributes>,
+}
+
+/// A change to the graph. Applying one returns the edit that reverts it.
+#[derive(Clone)]
+pub enum Edit {
+    /// Puts identifiers back to these transforms.
+    Transforms(Vec<(Entity, Transform)>),
+    /// Puts identifiers back to these transforms and pins or unpins them.
+    Placements(Vec<(Entity, Transform, bool)>),
+    /// Despawns identifiers, every connection referencing them and the given connections.
+    Despawn {
+        identifiers: Vec<Entity>,
+        connections: Vec<Entity>,
+    },
+    /// Spawns identifiers and connections again.
+    Spawn {
+        identifiers: Vec<IdentifierRecord>,
+        connections: Vec<ConnectionRecord>,
+    },
+}
+
+impl Edit {
+    pub fn describe(&self) -> String {
+        match self {
+            Edit::Transforms(transforms) => format!("move {} identifiers", transforms.len()),
+            Edit::Placements(placements) => {
+                format!("move and pin {} identifiers", placements.len())
+            }
+            Edit::Despawn {
+                identifiers,
+                connections,
+            } => format!(
+                "remove {} identifiers and {} connections",
+                identifiers.len(),
+                connections.len()
+            ),
+            Edit::Spawn {
+                identifiers,
+                connections,
+            } => format!(
+                "restore {} identifiers and {} connections",
+                identifiers.len(),
+                connections.len()
+            ),
+        }
+    }
+}
+
+/// Undo and redo stacks. Each holds the edits that revert the ones made.
+#[derive(Resource, Default)]
+pub struct History {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+    /// Entities respawned by undo or redo, keyed by the entity they replace.
+    aliases: HashMap<Entity, Entity>,
+    /// Entities spawned by undo or redo, which are not new edits.
+    replayed: HashSet<Entity>,
+}
+
+impl History {
+    /// Records `revert`, the edit that undoes a change just made.
+    pub fn record(&mut self, revert: Edit) {
+        self.undo.push(revert);
+        if self.undo.len() > MAX_HISTORY {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    /// Forgets every edit, when a new graph replaces the current one. `entities`
+    /// are spawned as its starting point, which is not an edit either.
+    pub fn restart(&mut self, entities: impl IntoIterator<Item = Entity>) {
+        self.undo.clear();
+        self.redo.clear();
+        self.aliases.clear();
+        self.replayed = entities.into_iter().collect();
+    }
+
+    pub fn next_undo(&self) -> Option<&Edit> {
+        self.undo.last()
+    }
+
+    pub fn next_redo(&self) -> Option<&Edit> {
+        self.redo.last()
+    }
+
+    /// The entity currently standing for `entity`, which may have been respawned since.
+    fn resolve(&self, mut entity: Entity) -> Entity {
+        while let Some(&next) = self.aliases.get(&entity) {
+            entity = next;
+        }
+        entity
+    }
+}
+
+pub struct HistoryPlugin;
+
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut App) {
+        // After `Update`, so nothing is despawned while it is being highlighted.
+        app.init_resource::<History>().add_systems(
+            PostUpdate,
+            (delete_identifiers, undo_redo, record_spawns).chain(),
+        );
+    }
+}
+
+type IdentifierData<'a> = (
+    Entity,
+    &'a Identifier,
+    &'a mut Transform,
+    Option<&'a Attributes>,
+    Option<&'a IdentifierMaterial>,
+    Has<Pinned>,
+    Has<Hidden>,
+);
+
+type ConnectionData<'a> = (Entity, &'a Connection, Option<&'a Attributes>);
+
+fn delete_identifiers(
+    mut commands: Commands,
+    mut ev: EventReader<DeleteIdentifiersEvent>,
+    mut ev_deselect: EventWriter<DeselectIdentifierEvent>,
+    mut history: ResMut<History>,
+    my_assets: Res<MyAssets>,
+    configuration: Res<Configuration>,
+    mut identifier_query: Query<IdentifierData>,
+    connection_query: Query<ConnectionData>,
+) {
+    for e in ev.read() {
+        let revert = apply(
+            Edit::Despawn {
+                identifiers: e.0.clone(),
+                connections: vec![],
+            },
+            &mut history,
+            &mut commands,
+            &my_assets,
+            &configuration,
+            &mut identifier_query,
+            &connection_query,
+        );
+        history.record(revert);
+        ev_deselect.send(DeselectIdentifierEvent);
+    }
+}
+
+fn undo_redo(
+    mut commands: Commands,
+    mut ev_undo: EventReader<UndoEvent>,
+    mut ev_redo: EventReader<RedoEvent>,
+    mut ev_deselect: EventWriter<DeselectIdentifierEvent>,
+    mut history: ResMut<History>,
+    my_assets: Res<MyAssets>,
+    configuration: Res<Configuration>,
+    mut identifier_query: Query<IdentifierData>,
+    connection_query: Query<ConnectionData>,
+) {
+    let undo = ev_undo.read().count();
+    let redo = ev_redo.read().count();
+    for redo in std::iter::repeat_n(false, undo).chain(std::iter::repeat_n(true, redo)) {
+        let edit = if redo {
+            history.redo.pop()
+        } else {
+            history.undo.pop()
+        };
+        let Some(edit) = edit else {
+            continue;
+        };
+        info!("{} {}", if redo { "Redo" } else { "Undo" }, edit.describe());
+        if matches!(edit, Edit::Despawn { .. }) {
+            // The selection may be among them.
+            ev_deselect.send(DeselectIdentifierEvent);
+        }
+        let revert = apply(
+            edit,
+            &mut history,
+            &mut commands,
+            &my_assets,
+            &configuration,
+            &mut identifier_query,
+            &connection_query,
+        );
+        if redo {
+            history.undo.push(revert);
+        } else {
+            history.redo.push(revert);
+        }
+    }
+}
+
+/// Spawned identifiers and connections are undone by despawning them.
+fn record_spawns(
+    mut history: ResMut<History>,
+    identifier_query: Query<Entity, Added<Identifier>>,
+    connection_query: Query<Entity, Added<Connection>>,
+) {
+    if identifier_query.is_empty() && connection_query.is_empty() {
+        return;
+    }
+    let identifiers: Vec<Entity> = identifier_query
+        .iter()
+        .filter(|entity| !history.replayed.remove(entity))
+        .collect();
+    let connections: Vec<Entity> = connection_query
+        .iter()
+        .filter(|entity| !history.replayed.remove(entity))
+        .collect();
+    if !identifiers.is_empty() || !connections.is_empty() {
+        history.record(Edit::Despawn {
+            identifiers,
+            connections,
+        });
+    }
+}
+
+fn apply(
+    edit: Edit,
+    history: &mut History,
+    commands: &mut Commands,
+    my_assets: &MyAssets,
+    configuration: &Configuration,
+    identifier_query: &mut Query<IdentifierData>,
+    connection_query: &Query<ConnectionData>,
+) -> Edit {
+    match edit {
+        Edit::Transforms(transforms) => {
+            let mut revert = vec![];
+            for (entity, transform) in transforms {
+                let entity = history.resolve(entity);
+                let Ok((_, _, mut current, ..)) = identifier_query.get_mut(entity) else {
+                    continue;
+                };
+                revert.push((entity, *current));
+                *current = transform;
+                commands
+                    .entity(entity)
+                    .remove::<(EasingComponent<Transform>, EasingChainComponent<Transform>)>();
+            }
+            Edit::Transforms(revert)
+        }
+        Edit::Placements(placements) => {
+            let mut revert = vec![];
+            for (entity, transform, pinned) in placements {
+                let entity = history.resolve(entity);
+                let Ok((_, _, mut current, _, _, was_pinned, _)) = identifier_query.get_mut(entity)
+                else {
+                    continue;
+                };
+                revert.push((entity, *current, was_pinned));
+                *current = transform;
+                let mut entity = commands.entity(entity);
+                entity.remove::<(EasingComponent<Transform>, EasingChainComponent<Transform>)>();
+                if pinned {
+                    entity.insert(Pinned);
+                } else {
+                    entity.remove::<Pinned>();
+                }
+            }
+            Edit::Placements(revert)
+        }
+        Edit::Despawn {
+            identifiers,
+            connections,
+        } => {
+            let identifiers: BTreeSet<Entity> = identifiers
+                .into_iter()
+                .map(|entity| history.resolve(entity))
+                .filter(|&entity| identifier_query.contains(entity))
+                .collect();
+            let connections: BTreeSet<Entity> = connections
+                .into_iter()
+                .map(|entity| history.resolve(entity))
+                .collect();
+            let connections: Vec<ConnectionRecord> = connection_query
+                .iter()
+                .filter(|(entity, connection, _)| {
+                    connections.contains(entity)
+                        || identifiers.contains(&connection.from)
+                        || identifiers.contains(&connection.to)
+                })
+                .map(|(entity, &connection, attributes)| ConnectionRecord {
+                    entity,
+                    connection,
+                    attributes: attributes.cloned(),
+                })
+                .collect();
+            let identifiers: Vec<IdentifierRecord> = identifiers
+                .into_iter()
+                .filter_map(|entity| identifier_query.get(entity).ok())
+                .map(
+                    |(entity, identifier, transform, attributes, material, pinned, hidden)| {
+                        IdentifierRecord {
+                            entity,
+                            identifier: identifier.clone(),
+                            transform: *transform,
+                            attributes: attributes.cloned(),
+                            material: material.cloned(),
+                            pinned,
+                            hidden,
+                        }
+                    },
+                )
+                .collect();
+
+            for record in connections.iter() {
+                commands.entity(record.entity).despawn_recursive();
+            }
+            for record in identifiers.iter() {
+                commands.entity(record.entity).despawn_recursive();
+            }
+            Edit::Spawn {
+                identifiers,
+                connections,
+            }
+        }
+        Edit::Spawn {
+            identifiers,
+            connections,
+        } => {
+            let mut positions = HashMap::new();
+            let mut spawned_identifiers = vec![];
+            for record in identifiers {
+                let entity = spawn_identifier(
+                    commands,
+                    my_assets,
+                    configuration,
+                    record.identifier,
+                    record.transform.translation,
+                    record.transform.scale.x,
+                );
+                if let Some(attributes) = record.attributes {
+                    commands.entity(entity).insert(attributes);
+                }
+                if let Some(material) = record.material {
+                    commands
+                        .entity(entity)
+                        .insert((material.0.clone(), material));
+                }
+                if record.pinned {
+                    commands.entity(entity).insert(Pinned);
+                }
+                if record.hidden {
+                    commands.entity(entity).insert((Hidden, Visibility::Hidden));
+                }
+                let replaced = history.resolve(record.entity);
+                history.aliases.insert(replaced, entity);
+                history.replayed.insert(entity);
+                positions.insert(entity, record.transform.translation);
+                spawned_identifiers.push(entity);
+            }
+
+            let mut spawned_connections = vec![];
+            for record in connections {
+                let from = history.resolve(record.connection.from);
+                let to = history.resolve(record.connection.to);
+                let position = |entity: Entity| {
+                    positions.get(&entity).copied().or_else(|| {
+                        identifier_query
+                            .get(entity)
+                            .ok()
+                            .map(|(_, _, transform, ..)| transform.translation)
+                    })
+                };
+                let (Some(from_position), Some(to_position)) = (position(from), position(to))
+                else {
+                    continue;
+                };
+                let entity = spawn_connection(
+                    commands,
+                    my_assets,
+                    configuration,
+                    Connection {
+                        from,
+                        to,
+                        ..record.connection
+                    },
+                    from_position,
+                    to_position,
+                );
+                if let Some(attributes) = record.attributes {
+                    commands.entity(entity).insert(attributes);
+                }
+                let replaced = history.resolve(record.entity);
+                history.aliases.insert(replaced, entity);
+                history.replayed.insert(entity);
+                spawned_connections.push(entity);
+            }
+            Edit::Despawn {
+                identifiers: spawned_identifiers,
+                connections: spawned_connections,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::EventsPlugin;
+    use crate::identifiers::ConnectionKind;
+    use bevy_easings::EasingsPlugin;
+    use std::time::Duration;
+
+    fn app() -> App {
+        let mut app = App::new();
+        // Time only moves when `redo` advances it past the animation.
+        app.add_plugins((EasingsPlugin, EventsPlugin, HistoryPlugin))
+            .init_resource::<Time>()
+            .init_resource::<MyAssets>()
+            .init_resource::<Configuration>();
+        app
+    }
+
+    fn spawn(app: &mut App, key: &str, x: f32) -> Entity {
+        app.world
+            .spawn((
+                Identifier {
+                    key: key.to_string(),
+                    label: None,
+                },
+                Transform::from_xyz(x, 0.0, 0.0),
+            ))
+            .id()
+    }
+
+    /// Keys and x coordinates of the identifiers, and the keys each connection joins.
+    fn graph(app: &mut App) -> (Vec<(String, f32)>, Vec<(String, String)>) {
+        let mut identifiers: Vec<(String, f32)> = app
+            .world
+            .query::<(&Identifier, &Transform)>()
+            .iter(&app.world)
+            .map(|(identifier, transform)| (identifier.key.clone(), transform.translation.x))
+            .collect();
+        identifiers.sort_by(|a, b| a.0.cmp(&b.0));
+        let connections: Vec<(Entity, Entity)> = app
+            .world
+            .query::<&Connection>()
+            .iter(&app.world)
+            .map(|connection| (connection.from, connection.to))
+            .collect();
+        let key = |entity: Entity| app.world.get::<Identifier>(entity).unwrap().key.clone();
+        let connections = connections
+            .into_iter()
+            .map(|(from, to)| (key(from), key(to)))
+            .collect();
+        (identifiers, connections)
+    }
+
+    fn undo(app: &mut App) {
+        app.world.send_event(UndoEvent);
+        app.update();
+    }
+
+    fn redo(app: &mut App) {
+        app.world.send_event(RedoEvent);
+        app.update();
+        // Respawned identifiers ease into place.
+        let duration = app.world.resource::<Configuration>().animation_duration;
+        app.world
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_secs(duration));
+        app.update();
+    }
+
+    #[test]
+    fn spawn_undo_redo_round_trip() {
+        let mut app = app();
+        let a = spawn(&mut app, "a", 1.0);
+        let b = spawn(&mut app, "b", 2.0);
+        app.world.spawn(Connection {
+            from: a,
+            to: b,
+            weight: 1.0,
+            kind: ConnectionKind::Generic,
+        });
+        app.update();
+        let spawned = graph(&mut app);
+        assert_eq!(spawned.0.len(), 2);
+        assert_eq!(spawned.1.len(), 1);
+
+        undo(&mut app);
+        assert_eq!(graph(&mut app), (vec![], vec![]));
+        assert!(app.world.resource::<History>().next_undo().is_none());
+
+        redo(&mut app);
+        assert_eq!(graph(&mut app), spawned);
+        // The respawned entities stand in for the old ones, so undoing again works.
+        undo(&mut app);
+        assert_eq!(graph(&mut app), (vec![], vec![]));
+        redo(&mut app);
+        assert_eq!(graph(&mut app), spawned);
+    }
+
+    #[test]
+    fn a_new_edit_clears_redo() {
+        let mut app = app();
+        let a = spawn(&mut app, "a", 1.0);
+        app.update();
+        app.world
+            .resource_mut::<History>()
+            .record(Edit::Transforms(vec![(
+                a,
+                Transform::from_xyz(1.0, 0.0, 0.0),
+            )]));
+        app.world.get_mut::<Transform>(a).unwrap().translation.x = 3.0;
+
+        undo(&mut app);
+        assert_eq!(graph(&mut app).0, vec![("a".to_string(), 1.0)]);
+        assert!(app.world.resource::<History>().next_redo().is_some());
+
+        spawn(&mut app, "b", 2.0);
+        app.update();
+        assert!(app.world.resource::<History>().next_redo().is_none());
+        redo(&mut app);
+        assert_eq!(graph(&mut app).0.len(), 2);
+    }
+
+    #[test]
+    fn placements_undo_the_pin() {
+        let mut app = app();
+        let a = spawn(&mut app, "a", 1.0);
+        app.update();
+        app.world
+            .resource_mut::<History>()
+            .record(Edit::Placements(vec![(
+                a,
+                Transform::from_xyz(1.0, 0.0, 0.0),
+                false,
+            )]));
+        app.world.entity_mut(a).insert(Pinned);
+        app.world.get_mut::<Transform>(a).unwrap().translation.x = 3.0;
+
+        undo(&mut app);
+        assert_eq!(app.world.get::<Transform>(a).unwrap().translation.x, 1.0);
+        assert!(!app.world.entity(a).contains::<Pinned>());
+        redo(&mut app);
+        assert_eq!(app.world.get::<Transform>(a).unwrap().translation.x, 3.0);
+        assert!(app.world.entity(a).contains::<Pinned>());
+    }
+}
@@ -1,37 +1,244 @@
 use bevy::prelude::*;
+use bevy_easings::*;
+use bevy_mod_picking::prelude::*;
+use bevy_mod_picking::PickableBundle;
 use bevy_panorbit_camera::PanOrbitCamera;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 
 use crate::{
     assets::MyAssets,
+    edit::EditMode,
     events::{
-        DeselectIdentifierEvent, SelectIdentifierEvent, SelectRandomConnectedIdentifierEvent,
-        SelectRandomIdentifierEvent,
+        DeselectIdentifierEvent, DeselectTargetIdentifierEvent, ExpandNeighbourhoodEvent,
+        SelectIdentifierEvent, SelectIdentifiersEvent, SelectRandomConnectedIdentifierEvent,
+        SelectRandomIdentifierEvent, SelectTargetIdentifierEvent, ToggleSelectionEvent,
+        UnpinAllEvent,
     },
+    graph_index::GraphIndex,
+    history::{Edit, History},
+    layout::EgoLayout,
+    resources::{Configuration, SeededRng},
     util::calculate_from_translation_and_focus,
 };
 
-#[derive(Component)]
-pub struct Identifier;
+#[derive(Component, Clone, Debug, Reflect)]
+pub struct Identifier {
+    /// Key the identifier is known by in the data it was loaded from.
+    pub key: String,
+    /// Human readable name, from the `label` attribute of imported files.
+    pub label: Option<String>,
+}
 
 #[derive(Component, Copy, Clone, Debug, Reflect)]
 pub struct Connection {
     pub from: Entity,
     pub to: Entity,
+    pub weight: f32,
+    pub kind: ConnectionKind,
+}
+
+/// What a connection stands for. Credentials point from the issuer to the subject,
+/// presentations from the holder to the verifier and messages from sender to recipient.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionKind {
+    #[default]
+    Generic,
+    Credential,
+    Presentation,
+    Message,
+}
+
+impl ConnectionKind {
+    pub const ALL: [ConnectionKind; 4] = [
+        ConnectionKind::Generic,
+        ConnectionKind::Credential,
+        ConnectionKind::Presentation,
+        ConnectionKind::Message,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConnectionKind::Generic => "generic",
+            ConnectionKind::Credential => "credential",
+            ConnectionKind::Presentation => "presentation",
+            ConnectionKind::Message => "message",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+}
+
+/// Free-form key/value data carried over from imported files, on identifiers and connections.
+#[derive(Component, Clone, Debug, Default)]
+pub struct Attributes(pub BTreeMap<String, String>);
+
+/// Material an identifier with its own colour shows when it is not highlighted.
+/// Identifiers without one use `MyAssets::identifier_material_handle`.
+#[derive(Component, Clone, Debug)]
+pub struct IdentifierMaterial(pub Handle<StandardMaterial>);
+
+/// Identifier the layouts leave where it is.
+#[derive(Component, Clone, Copy, Debug, Default, Reflect)]
+#[reflect(Component)]
+pub struct Pinned;
+
+/// Identifier hidden along with its connections, until shown again.
+#[derive(Component, Clone, Copy, Debug, Default, Reflect)]
+#[reflect(Component)]
+pub struct Hidden;
+
+/// Scale identifiers are spawned with before any metric resizes them.
+pub const IDENTIFIER_SCALE: f32 = 0.5;
+
+/// Spawns an identifier that grows in at `position`.
+pub fn spawn_identifier(
+    commands: &mut Commands,
+    my_assets: &MyAssets,
+    configuration: &Configuration,
+    identifier: Identifier,
+    position: Vec3,
+    scale: f32,
+) -> Entity {
+    commands
+        .spawn((
+            MaterialMeshBundle {
+                mesh: my_assets.identifier_mesh_handle.clone(),
+                material: my_assets.identifier_material_handle.clone(),
+                ..Default::default()
+            },
+            Transform::from_translation(position)
+                .with_scale(Vec3::new(0.0001, 0.0001, 0.0001))
+                .ease_to(
+                    Transform::from_translation(position).with_scale(Vec3::splat(scale)),
+                    bevy_easings::EaseFunction::QuadraticOut,
+                    bevy_easings::EasingType::Once {
+                        duration: std::time::Duration::from_secs(configuration.animation_duration),
+                    },
+                ),
+            identifier,
+            PickableBundle::default(),
+            On::<Pointer<Click>>::run(
+                |event: Listener<Pointer<Click>>,
+                 keys: Res<Input<KeyCode>>,
+                 mut ev: EventWriter<SelectIdentifierEvent>,
+                 mut ev_toggle: EventWriter<ToggleSelectionEvent>,
+                 mut ev_target: EventWriter<SelectTargetIdentifierEvent>| {
+                    info!("The pointer clicked entity {:?}", event.target);
+                    // Shift-click adds to or removes from the selection,
+                    // ctrl-click picks the other end of a path.
+                    if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+                        ev_toggle.send(ToggleSelectionEvent(event.target));
+                    } else if keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+                        ev_target.send(SelectTargetIdentifierEvent(event.target));
+                    } else {
+                        ev.send(SelectIdentifierEvent(event.target));
+                    }
+                },
+            ),
+        ))
+        .id()
+}
+
+/// Spawns a connection between identifiers at `from_position` and `to_position`.
+pub fn spawn_connection(
+    commands: &mut Commands,
+    my_assets: &MyAssets,
+    configuration: &Configuration,
+    connection: Connection,
+    from_position: Vec3,
+    to_position: Vec3,
+) -> Entity {
+    let mid_point = from_position.lerp(to_position, 0.5);
+    let distance = from_position.distance(to_position);
+    let rotation = Quat::from_rotation_arc(Vec3::Y, (to_position - from_position).normalize());
+
+    commands
+        .spawn((
+            MaterialMeshBundle {
+                mesh: my_assets.connection_mesh_handle.clone(),
+                material: my_assets.connection_material_handle.clone(),
+                visibility: Visibility::Visible,
+                ..Default::default()
+            },
+            Transform::from_translation(mid_point)
+                .with_rotation(rotation)
+                .with_scale(Vec3::new(1.0, 0.00001, 1.0))
+                .ease_to(
+                    Transform::from_translation(mid_point)
+                        .with_rotation(rotation)
+                        .with_scale(Vec3::new(1.0, distance, 1.0)),
+                    bevy_easings::EaseFunction::QuadraticInOut,
+                    bevy_easings::EasingType::Once {
+                        duration: std::time::Duration::from_secs(configuration.animation_duration),
+                    },
+                ),
+            connection,
+        ))
+        .id()
 }
 
 #[derive(Reflect, Resource, Default)]
 #[reflect(Resource, Default)]
 pub struct SelectedIdentifier(pub Option<Entity>);
 
+/// Every selected identifier. `SelectedIdentifier` is the one the camera
+/// follows and is always part of the set; selecting a single identifier
+/// replaces the set.
+#[derive(Resource, Default)]
+pub struct Selection(pub BTreeSet<Entity>);
+
+/// How far from the selection identifiers and connections are highlighted.
+#[derive(Reflect, Resource)]
+#[reflect(Resource)]
+pub struct Neighbourhood {
+    /// Connections between the selection and the furthest highlighted identifier.
+    pub depth: usize,
+}
+
+impl Default for Neighbourhood {
+    fn default() -> Self {
+        Self { depth: 1 }
+    }
+}
+
+/// Largest `Neighbourhood::depth`, each hop up to it has its own colour.
+pub const MAX_NEIGHBOURHOOD_DEPTH: usize = 6;
+
+/// Other end of the shortest path from `SelectedIdentifier`.
+#[derive(Reflect, Resource, Default)]
+#[reflect(Resource, Default)]
+pub struct TargetIdentifier(pub Option<Entity>);
+
 pub struct IdentifiersPlugin;
 
 impl Plugin for IdentifiersPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<SelectedIdentifier>()
             .register_type::<SelectedIdentifier>()
+            .init_resource::<TargetIdentifier>()
+            .register_type::<TargetIdentifier>()
+            .init_resource::<Selection>()
+            .init_resource::<Neighbourhood>()
+            .register_type::<Neighbourhood>()
+            .register_type::<Identifier>()
+            .register_type::<Pinned>()
+            .register_type::<Hidden>()
             .add_systems(Update, select_identifier)
             .add_systems(Update, deselect_identifier)
+            .add_systems(Update, toggle_selection.after(select_identifier))
+            .add_systems(Update, select_identifiers.after(select_identifier))
+            .add_systems(Update, expand_neighbourhood)
+            .add_systems(Update, despawn_dangling_connections)
+            .add_systems(Update, drag_identifier)
+            .add_systems(Update, unpin_all)
+            .add_systems(Update, select_target_identifier)
+            .add_systems(Update, deselect_target_identifier)
             .add_systems(Update, select_random_identifier)
             .add_systems(Update, select_random_connected_identifier)
             .add_systems(Update, update_identifiers_and_connections)
@@ -42,6 +249,7 @@ impl Plugin for IdentifiersPlugin {
 fn select_random_connected_identifier(
     mut selected_identifier: ResMut<SelectedIdentifier>,
     mut ev_rnd_c: EventReader<SelectRandomConnectedIdentifierEvent>,
+    mut seeded_rng: ResMut<SeededRng>,
     connection_query: Query<(Entity, &Connection), With<Connection>>,
 ) {
     #[allow(deprecated)]
@@ -62,7 +270,7 @@ fn select_random_connected_identifier(
             return;
         }
         // randomly select identifier from connections that is not the original selected identifier
-        let mut rng = rand::thread_rng();
+        let rng = seeded_rng.rng();
         if let Some(random_connection) = connections.get(rng.gen_range(0..connections.len())) {
             if let Some(currently_selected_identifier) = selected_identifier.0 {
                 if random_connection.1.from == currently_selected_identifier {
@@ -78,20 +286,17 @@ fn select_random_connected_identifier(
 fn select_random_identifier(
     mut selected_identifier: ResMut<SelectedIdentifier>,
     mut ev_rnd: EventReader<SelectRandomIdentifierEvent>,
-    query: Query<(Entity, &Identifier)>,
+    mut seeded_rng: ResMut<SeededRng>,
+    graph_index: Res<GraphIndex>,
 ) {
     #[allow(deprecated)]
     for _ in ev_rnd.iter() {
-        let identifier_count = query.iter().count() as u32;
-        let mut rng = rand::thread_rng();
-        let random_identifier = query
-            .iter()
-            .nth(rng.gen_range(0..identifier_count as usize));
-
-        if let Some((entity, _)) = random_identifier {
-            selected_identifier.0 = Some(entity);
-            info!("Selecting identifier {:?}", entity);
+        if graph_index.len() == 0 {
+            continue;
         }
+        let entity = graph_index.entity(seeded_rng.rng().gen_range(0..graph_index.len()));
+        selected_identifier.0 = Some(entity);
+        info!("Selecting identifier {:?}", entity);
     }
 }
 
@@ -107,18 +312,185 @@ fn select_identifier(
 
 fn deselect_identifier(
     mut selected_identifier: ResMut<SelectedIdentifier>,
+    mut target_identifier: ResMut<TargetIdentifier>,
     mut ev: EventReader<DeselectIdentifierEvent>,
 ) {
     for _ in ev.read() {
         selected_identifier.0 = None;
+        target_identifier.0 = None;
         info!("Deselecting identifier");
     }
 }
 
+fn toggle_selection(
+    mut selected_identifier: ResMut<SelectedIdentifier>,
+    mut selection: ResMut<Selection>,
+    mut ev: EventReader<ToggleSelectionEvent>,
+) {
+    for event in ev.read() {
+        if selection.0.remove(&event.0) {
+            if selected_identifier.0 == Some(event.0) {
+                selected_identifier.0 = selection.0.last().copied();
+            }
+            info!("Removing identifier {:?} from the selection", event.0);
+        } else {
+            selection.0.insert(event.0);
+            selected_identifier.0 = Some(event.0);
+            info!("Adding identifier {:?} to the selection", event.0);
+        }
+    }
+}
+
+fn select_identifiers(
+    mut selected_identifier: ResMut<SelectedIdentifier>,
+    mut selection: ResMut<Selection>,
+    mut ev: EventReader<SelectIdentifiersEvent>,
+) {
+    for event in ev.read() {
+        if !event.add {
+            selection.0.clear();
+        }
+        selection.0.extend(event.entities.iter().copied());
+        // Keep the camera where it is when adding to a selection.
+        if !event.add || selected_identifier.0.is_none() {
+            selected_identifier.0 = event.entities.last().copied();
+        }
+        info!("Selecting {} identifiers", event.entities.len());
+    }
+}
+
+fn expand_neighbourhood(
+    mut neighbourhood: ResMut<Neighbourhood>,
+    mut ev: EventReader<ExpandNeighbourhoodEvent>,
+) {
+    for _ in ev.read() {
+        if neighbourhood.depth < MAX_NEIGHBOURHOOD_DEPTH {
+            neighbourhood.depth += 1;
+            info!(
+                "Highlighting {} hops around the selection",
+                neighbourhood.depth
+            );
+        }
+    }
+}
+
+/// Despawns connections whose identifier at either end was despawned.
+fn despawn_dangling_connections(
+    mut commands: Commands,
+    mut removed: RemovedComponents<Identifier>,
+    connection_query: Query<(Entity, &Connection)>,
+) {
+    let removed: HashSet<Entity> = removed.read().collect();
+    if removed.is_empty() {
+        return;
+    }
+    for (entity, connection) in connection_query.iter() {
+        if removed.contains(&connection.from) || removed.contains(&connection.to) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Moves a dragged identifier along the plane through it that faces the
+/// camera, and pins it so layouts leave it there.
+fn drag_identifier(
+    mut commands: Commands,
+    edit_mode: Res<EditMode>,
+    keys: Res<Input<KeyCode>>,
+    mut ev_start: EventReader<Pointer<DragStart>>,
+    mut ev_drag: EventReader<Pointer<Drag>>,
+    mut ev_end: EventReader<Pointer<DragEnd>>,
+    mut dragged: Local<Option<Entity>>,
+    mut history: ResMut<History>,
+    mut camera_query: Query<(&Camera, &GlobalTransform, &mut PanOrbitCamera)>,
+    mut identifier_query: Query<(&mut Transform, Has<Pinned>), With<Identifier>>,
+) {
+    let Ok((camera, camera_transform, mut pan_orbit)) = camera_query.get_single_mut() else {
+        return;
+    };
+    for event in ev_start.read() {
+        // Dragging connects identifiers in edit mode and selects with shift.
+        if edit_mode.enabled
+            || keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight])
+            || event.button != PointerButton::Primary
+        {
+            continue;
+        }
+        let Ok((transform, pinned)) = identifier_query.get(event.target) else {
+            continue;
+        };
+        history.record(Edit::Placements(vec![(event.target, *transform, pinned)]));
+        *dragged = Some(event.target);
+        pan_orbit.enabled = false;
+        commands
+            .entity(event.target)
+            .insert(Pinned)
+            .remove::<(EasingComponent<Transform>, EasingChainComponent<Transform>)>();
+    }
+    for event in ev_drag.read() {
+        if *dragged != Some(event.target) {
+            continue;
+        }
+        let Ok((mut transform, _)) = identifier_query.get_mut(event.target) else {
+            continue;
+        };
+        let normal = (pan_orbit.focus - camera_transform.translation()).normalize();
+        if let Some(position) = camera
+            .viewport_to_world(camera_transform, event.pointer_location.position)
+            .and_then(|ray| {
+                Some(ray.get_point(ray.intersect_plane(transform.translation, normal)?))
+            })
+        {
+            transform.translation = position;
+        }
+    }
+    for event in ev_end.read() {
+        if *dragged == Some(event.target) {
+            *dragged = None;
+            pan_orbit.enabled = true;
+        }
+    }
+}
+
+fn unpin_all(
+    mut commands: Commands,
+    mut ev: EventReader<UnpinAllEvent>,
+    pinned_query: Query<Entity, With<Pinned>>,
+) {
+    for _ in ev.read() {
+        for entity in pinned_query.iter() {
+            commands.entity(entity).remove::<Pinned>();
+        }
+        info!("Unpinned {} identifiers", pinned_query.iter().len());
+    }
+}
+
+fn select_target_identifier(
+    mut target_identifier: ResMut<TargetIdentifier>,
+    mut ev: EventReader<SelectTargetIdentifierEvent>,
+) {
+    for event in ev.read() {
+        target_identifier.0 = Some(event.0);
+        info!("Selecting target identifier {:?}", event.0);
+    }
+}
+
+fn deselect_target_identifier(
+    mut target_identifier: ResMut<TargetIdentifier>,
+    mut ev: EventReader<DeselectTargetIdentifierEvent>,
+) {
+    for _ in ev.read() {
+        target_identifier.0 = None;
+        info!("Deselecting target identifier");
+    }
+}
+
 fn zoom_camera_to_selected_identifier(
     selected_identifier: Res<SelectedIdentifier>,
+    ego_layout: Res<EgoLayout>,
+    configuration: Res<Configuration>,
     identifier_query: Query<&Transform, With<Identifier>>,
-    mut camera_q: Query<&mut PanOrbitCamera, With<PanOrbitCamera>>,
+    mut camera_q: Query<(&mut PanOrbitCamera, &Transform), Without<Identifier>>,
 ) {
     if !selected_identifier.is_changed() {
         return;
@@ -126,111 +498,159 @@ fn zoom_camera_to_selected_identifier(
 
     if let Some(id) = selected_identifier.0 {
         if let Ok(&identifier_transform) = identifier_query.get(id) {
-            if let Ok(mut camera) = camera_q.get_single_mut() {
+            if let Ok((mut camera, camera_transform)) = camera_q.get_single_mut() {
                 let direction = identifier_transform.translation - Vec3::ZERO;
-                let normalized_direction = direction.normalize();
-                let desired_distance = 4.0;
+                // An identifier at the origin, as the ego layout puts it, is
+                // looked at from where the camera already is.
+                let normalized_direction = direction.try_normalize().unwrap_or_else(|| {
+                    (camera_transform.translation - camera.focus)
+                        .try_normalize()
+                        .unwrap_or(Vec3::Z)
+                });
+                // The ego layout moves the selected identifier to the origin
+                // and its neighbourhood around it, so frame all of it.
+                let (focus, desired_distance) = if ego_layout.enabled {
+                    (Vec3::ZERO, configuration.container_size * 2.0)
+                } else {
+                    (identifier_transform.translation, 4.0)
+                };
 
-                let camera_position =
-                    identifier_transform.translation + normalized_direction * desired_distance;
+                let camera_position = focus + normalized_direction * desired_distance;
 
-                let (alpha, beta, radius) = calculate_from_translation_and_focus(
-                    camera_position,
-                    identifier_transform.translation,
-                );
+                let (alpha, beta, radius) =
+                    calculate_from_translation_and_focus(camera_position, focus);
                 camera.target_alpha = alpha;
                 camera.target_beta = beta;
                 camera.target_radius = radius;
-                camera.target_focus = identifier_transform.translation;
+                camera.target_focus = focus;
             };
         };
     }
 }
 
-fn update_identifiers_and_connections(
+pub fn update_identifiers_and_connections(
     mut commands: Commands,
     my_assets: ResMut<MyAssets>,
     // configuration: Res<Configuration>,
     selected_identifier: Res<SelectedIdentifier>,
-    identifier_query: Query<(Entity, &Transform), With<Identifier>>,
+    mut selection: ResMut<Selection>,
+    neighbourhood: Res<Neighbourhood>,
+    identifier_query: Query<(Entity, &Transform, Has<Hidden>), With<Identifier>>,
+    material_query: Query<&IdentifierMaterial>,
     connection_query: Query<(Entity, &Connection), With<Connection>>,
 ) {
-    if !selected_identifier.is_changed() {
+    if !selected_identifier.is_changed() && !selection.is_changed() && !neighbourhood.is_changed() {
         return;
     };
+    // Anything that sets the selected identifier on its own selects just that one.
+    if selected_identifier.is_changed() && !selection.is_changed() {
+        selection.0 = selected_identifier.0.into_iter().collect();
+    }
 
-    if let Some(id) = selected_identifier.0 {
-        // if let Ok((identifier, &identifier_transform)) = identifier_query.get(id) {
-        //     commands.entity(identifier).insert(MaterialMeshBundle {
-        //         mesh: my_assets.identifier_mesh_handle.clone(),
-        //         material: my_assets.identifier_selected_material_handle.clone(),
-        //         transform: identifier_transform.with_scale(Vec3::new(1.0, 1.0, 1.0)),
-        //         ..Default::default()
-        //     });
-        // }
-
-        // scale all other identifiers
-        for (identifier, &identifier_transform) in
-            identifier_query.iter().filter(|(entity, _)| *entity != id)
-        {
-            commands.entity(identifier).insert(MaterialMeshBundle {
-                mesh: my_assets.identifier_mesh_handle.clone(),
-                material: my_assets.identifier_material_handle.clone(),
-                transform: identifier_transform,
-                //.with_scale(Vec3::new(0.5, 0.5, 0.5)),
-                ..Default::default()
-            });
-        }
+    highlight_selection(
+        &mut commands,
+        &my_assets,
+        &selection.0,
+        neighbourhood.depth,
+        &identifier_query,
+        &material_query,
+        &connection_query,
+    );
+}
 
-        // show only connections that have from or to as  selected identifier
-        for (connection_entity, &connection) in connection_query.iter() {
-            if connection.from == id || connection.to == id {
-                if let Ok((entity, &transform)) = identifier_query.get(connection.to) {
-                    if entity != id {
-                        commands.entity(entity).insert(MaterialMeshBundle {
-                            mesh: my_assets.identifier_mesh_handle.clone(),
-                            material: my_assets.identifier_connected_material_handle.clone(),
-                            transform, //.with_scale(Vec3::new(1.0, 1.0, 1.0)),
-                            ..Default::default()
-                        });
-                    }
-                }
-                if let Ok((entity, &transform)) = identifier_query.get(connection.from) {
-                    if entity != id {
-                        commands.entity(entity).insert(MaterialMeshBundle {
-                            mesh: my_assets.identifier_mesh_handle.clone(),
-                            material: my_assets.identifier_connected_material_handle.clone(),
-                            transform, //.with_scale(Vec3::new(1.0, 1.0, 1.0)),
-                            ..Default::default()
-                        });
-                    }
-                }
+/// Highlights `selection` and the identifiers up to `depth` connections away,
+/// coloured by distance, and hides the connections outside that neighbourhood.
+/// Shows everything in its own material when nothing is selected. Hidden
+/// identifiers and their connections stay hidden.
+pub fn highlight_selection(
+    commands: &mut Commands,
+    my_assets: &MyAssets,
+    selection: &BTreeSet<Entity>,
+    depth: usize,
+    identifier_query: &Query<(Entity, &Transform, Has<Hidden>), With<Identifier>>,
+    material_query: &Query<&IdentifierMaterial>,
+    connection_query: &Query<(Entity, &Connection), With<Connection>>,
+) {
+    let base_material = |entity: Entity| {
+        material_query.get(entity).map_or_else(
+            |_| my_assets.identifier_material_handle.clone(),
+            |material| material.0.clone(),
+        )
+    };
+    let hidden = |entity: Entity| {
+        identifier_query
+            .get(entity)
+            .is_ok_and(|(_, _, hidden)| hidden)
+    };
 
-                commands
-                    .entity(connection_entity)
-                    .insert(Visibility::Visible);
-            } else {
-                commands
-                    .entity(connection_entity)
-                    .insert(Visibility::Hidden);
-            }
+    // Hops from the nearest selected identifier, following connections both ways
+    let mut neighbours: HashMap<Entity, Vec<Entity>> = HashMap::new();
+    for (_, connection) in connection_query.iter() {
+        neighbours
+            .entry(connection.from)
+            .or_default()
+            .push(connection.to);
+        neighbours
+            .entry(connection.to)
+            .or_default()
+            .push(connection.from);
+    }
+    let mut distances: HashMap<Entity, usize> =
+        selection.iter().map(|&entity| (entity, 0)).collect();
+    let mut queue: VecDeque<Entity> = selection.iter().copied().collect();
+    while let Some(entity) = queue.pop_front() {
+        let distance = distances[&entity];
+        if distance == depth {
+            continue;
         }
-    } else {
-        // show all identifiers
-        for (identifier, &identifier_transform) in identifier_query.iter() {
-            commands.entity(identifier).insert(MaterialMeshBundle {
-                mesh: my_assets.identifier_mesh_handle.clone(),
-                material: my_assets.identifier_material_handle.clone(),
-                transform: identifier_transform, //.with_scale(Vec3::new(1.0, 1.0, 1.0)),
-                ..Default::default()
-            });
+        for &next in neighbours.get(&entity).into_iter().flatten() {
+            if let Entry::Vacant(entry) = distances.entry(next) {
+                entry.insert(distance + 1);
+                queue.push_back(next);
+            }
         }
+    }
 
-        // show all connections
-        for (connection_entity, _) in connection_query.iter() {
-            commands
-                .entity(connection_entity)
-                .insert(Visibility::Visible);
-        }
+    for (connection_entity, &connection) in connection_query.iter() {
+        // Connections the search went along, so depth 1 shows those touching the selection.
+        let traversed = match (
+            distances.get(&connection.from),
+            distances.get(&connection.to),
+        ) {
+            (Some(&from), Some(&to)) => from.min(to) < depth,
+            _ => false,
+        };
+        let visible = (selection.is_empty() || traversed)
+            && !hidden(connection.from)
+            && !hidden(connection.to);
+        commands.entity(connection_entity).insert(if visible {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        });
+    }
+
+    for (identifier, &identifier_transform, _) in identifier_query.iter() {
+        // A single selected identifier keeps its own material, the members of a
+        // larger selection are marked so the set stands out.
+        let material = match distances.get(&identifier) {
+            Some(0) if selection.len() > 1 => my_assets.identifier_selected_material_handle.clone(),
+            Some(&hops) if hops > 0 => {
+                let handles = &my_assets.identifier_hop_material_handles;
+                handles[(hops - 1).min(handles.len() - 1)].clone()
+            }
+            _ => base_material(identifier),
+        };
+        commands.entity(identifier).insert(MaterialMeshBundle {
+            mesh: my_assets.identifier_mesh_handle.clone(),
+            material,
+            transform: identifier_transform,
+            visibility: if hidden(identifier) {
+                Visibility::Hidden
+            } else {
+                Visibility::Visible
+            },
+            ..Default::default()
+        });
     }
 }
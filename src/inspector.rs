@@ -0,0 +1,182 @@
+use bevy::prelude::*;
+use bevy_egui::EguiContext;
+use bevy_inspector_egui::egui;
+use bevy_window::PrimaryWindow;
+
+use crate::communities::Community;
+use crate::events::SelectIdentifierEvent;
+use crate::identifiers::{
+    Attributes, Connection, ConnectionKind, Hidden, Identifier, Pinned, SelectedIdentifier,
+};
+use crate::metrics::{Centralities, Metric};
+
+fn identifier_name(identifier: &Identifier) -> String {
+    match &identifier.label {
+        Some(label) => format!("{} ({})", label, identifier.key),
+        None => identifier.key.clone(),
+    }
+}
+
+fn attributes_grid(ui: &mut egui::Ui, id: impl std::hash::Hash, attributes: &Attributes) {
+    egui::Grid::new(id).striped(true).show(ui, |ui| {
+        for (key, value) in attributes.0.iter() {
+            ui.label(key);
+            ui.label(value);
+            ui.end_row();
+        }
+    });
+}
+
+/// Attributes, degree, metrics and connections of the selected identifier.
+/// Clicking a neighbour selects it.
+pub fn inspector_ui(
+    query: Query<&mut EguiContext, With<PrimaryWindow>>,
+    selected_identifier: Res<SelectedIdentifier>,
+    identifier_query: Query<(
+        &Identifier,
+        &Transform,
+        Option<&Attributes>,
+        Option<&Centralities>,
+        Option<&Community>,
+        Has<Pinned>,
+        Has<Hidden>,
+    )>,
+    connection_query: Query<(Entity, &Connection, Option<&Attributes>)>,
+    mut ev: EventWriter<SelectIdentifierEvent>,
+) {
+    let mut egui_context = query.single().clone();
+
+    egui::Window::new("Inspector")
+        .vscroll(false)
+        .hscroll(false)
+        .default_width(250.0)
+        .resizable(false)
+        .show(egui_context.get_mut(), |ui| {
+            let Some(entity) = selected_identifier.0 else {
+                ui.label("Click an identifier to inspect it.");
+                return;
+            };
+            let Ok((identifier, transform, attributes, centralities, community, pinned, hidden)) =
+                identifier_query.get(entity)
+            else {
+                return;
+            };
+
+            ui.heading(identifier.label.as_deref().unwrap_or("Identifier"));
+            ui.label(&identifier.key);
+            let position = transform.translation;
+            ui.label(format!(
+                "Position ({:.2}, {:.2}, {:.2})",
+                position.x, position.y, position.z
+            ));
+            let mut flags = vec![];
+            if pinned {
+                flags.push("pinned".to_string());
+            }
+            if hidden {
+                flags.push("hidden".to_string());
+            }
+            if let Some(community) = community {
+                flags.push(format!("community {}", community.0));
+            }
+            if !flags.is_empty() {
+                ui.label(flags.join(", "));
+            }
+
+            // Outgoing connections first, then incoming ones.
+            let mut connections: Vec<(bool, Entity, &Connection, Option<&Attributes>)> =
+                connection_query
+                    .iter()
+                    .filter_map(|(connection_entity, connection, attributes)| {
+                        let outgoing = connection.from == entity;
+                        (outgoing || connection.to == entity).then_some((
+                            outgoing,
+                            connection_entity,
+                            connection,
+                            attributes,
+                        ))
+                    })
+                    .collect();
+            connections
+                .sort_by_key(|(outgoing, connection_entity, ..)| (!outgoing, *connection_entity));
+            let out_degree = connections
+                .iter()
+                .filter(|(outgoing, ..)| *outgoing)
+                .count();
+            ui.label(format!(
+                "Degree {} ({} out, {} in)",
+                connections.len(),
+                out_degree,
+                connections.len() - out_degree
+            ));
+
+            ui.collapsing("Attributes", |ui| match attributes {
+                Some(attributes) if !attributes.0.is_empty() => {
+                    attributes_grid(ui, "inspector_attributes", attributes)
+                }
+                _ => {
+                    ui.label("None");
+                }
+            });
+
+            ui.collapsing("Metrics", |ui| match centralities {
+                Some(centralities) => {
+                    egui::Grid::new("inspector_metrics")
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for metric in Metric::ALL {
+                                ui.label(metric.name());
+                                ui.label(format!("{:.4}", centralities.get(metric)));
+                                ui.end_row();
+                            }
+                        });
+                }
+                None => {
+                    ui.label("Not computed yet, see the Metrics window.");
+                }
+            });
+
+            egui::CollapsingHeader::new(format!("Connections ({})", connections.len()))
+                .default_open(true)
+                .show(ui, |ui| {
+                    egui::ScrollArea::vertical()
+                        .max_height(300.0)
+                        .show(ui, |ui| {
+                            for (outgoing, connection_entity, connection, attributes) in
+                                connections.iter()
+                            {
+                                let neighbour = if *outgoing {
+                                    connection.to
+                                } else {
+                                    connection.from
+                                };
+                                let Ok((neighbour_identifier, ..)) =
+                                    identifier_query.get(neighbour)
+                                else {
+                                    continue;
+                                };
+                                ui.horizontal(|ui| {
+                                    ui.label(if *outgoing { "→" } else { "←" });
+                                    let response = ui.link(identifier_name(neighbour_identifier));
+                                    let response = match attributes {
+                                        Some(attributes) if !attributes.0.is_empty() => response
+                                            .on_hover_ui(|ui| {
+                                                attributes_grid(ui, connection_entity, attributes)
+                                            }),
+                                        _ => response,
+                                    };
+                                    if response.clicked() {
+                                        ev.send(SelectIdentifierEvent(neighbour));
+                                    }
+                                    if connection.kind != ConnectionKind::Generic {
+                                        ui.label(connection.kind.name());
+                                    }
+                                    if connection.weight != 1.0 {
+                                        ui.label(format!("weight {}", connection.weight));
+                                    }
+                                });
+                            }
+                        });
+                });
+        });
+}
@@ -0,0 +1,127 @@
+use bevy::prelude::*;
+use bevy::tasks::AsyncComputeTaskPool;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// How far a background job has got. Shared between the job, which reports
+/// progress and checks for cancellation, and the UI.
+pub struct Progress {
+    name: &'static str,
+    /// `f32` bits of the completed fraction.
+    fraction: AtomicU32,
+    cancelled: AtomicBool,
+    finished: AtomicBool,
+}
+
+impl Progress {
+    pub fn new(name: &'static str) -> Self {
+        Progress {
+            name,
+            fraction: AtomicU32::new(0.0f32.to_bits()),
+            cancelled: AtomicBool::new(false),
+            finished: AtomicBool::new(false),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn fraction(&self) -> f32 {
+        f32::from_bits(self.fraction.load(Ordering::Relaxed))
+    }
+
+    pub fn set(&self, fraction: f32) {
+        self.fraction
+            .store(fraction.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Long computations check this between steps and return early when set.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Background jobs started by any system, for the progress indicator.
+#[derive(Resource, Default)]
+pub struct Jobs(Vec<Arc<Progress>>);
+
+impl Jobs {
+    pub fn running(&self) -> impl Iterator<Item = &Progress> {
+        self.0
+            .iter()
+            .map(Arc::as_ref)
+            .filter(|progress| !progress.finished.load(Ordering::Relaxed))
+    }
+
+    /// Runs `work` on the `AsyncComputeTaskPool`. The result is collected
+    /// with [`poll`] from a system that keeps the returned job.
+    pub fn spawn<T: Send + 'static>(
+        &mut self,
+        name: &'static str,
+        work: impl FnOnce(&Progress) -> T + Send + 'static,
+    ) -> Job<T> {
+        self.0
+            .retain(|progress| !progress.finished.load(Ordering::Relaxed));
+        let progress = Arc::new(Progress::new(name));
+        self.0.push(progress.clone());
+
+        let job = Job {
+            progress: progress.clone(),
+            result: Arc::new(Mutex::new(None)),
+        };
+        // The result goes through the mutex rather than the task, as wasm has
+        // no task handle to read it from.
+        let result = job.result.clone();
+        AsyncComputeTaskPool::get()
+            .spawn(async move {
+                let value = work(&progress);
+                if !progress.is_cancelled() {
+                    *result.lock().unwrap() = Some(value);
+                }
+            })
+            .detach();
+        job
+    }
+}
+
+/// A computation running in the background. Dropping it cancels the
+/// computation and removes it from the progress indicator.
+pub struct Job<T> {
+    progress: Arc<Progress>,
+    result: Arc<Mutex<Option<T>>>,
+}
+
+impl<T> Drop for Job<T> {
+    fn drop(&mut self) {
+        self.progress.cancel();
+        self.progress.finished.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Takes the result of `job` once it is complete and clears the slot. A job
+/// cancelled from the UI is cleared without a result.
+pub fn poll<T>(job: &mut Option<Job<T>>) -> Option<T> {
+    let running = job.as_ref()?;
+    if running.progress.is_cancelled() {
+        *job = None;
+        return None;
+    }
+    let result = running.result.lock().unwrap().take();
+    if result.is_some() {
+        *job = None;
+    }
+    result
+}
+
+pub struct JobsPlugin;
+
+impl Plugin for JobsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Jobs>();
+    }
+}
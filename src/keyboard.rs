@@ -1,5 +1,9 @@
 use bevy::prelude::*;
+use bevy_egui::EguiContext;
 use bevy_panorbit_camera::PanOrbitCamera;
+use bevy_window::PrimaryWindow;
+
+use crate::events::{RedoEvent, UndoEvent};
 pub struct KeyboardPlugin;
 
 impl Plugin for KeyboardPlugin {
@@ -12,7 +16,23 @@ fn keyboard_controls(
     time: Res<Time>,
     key_input: Res<Input<KeyCode>>,
     mut pan_orbit_query: Query<(&mut PanOrbitCamera, &mut Transform)>,
+    window_query: Query<&EguiContext, With<PrimaryWindow>>,
+    mut ev_undo: EventWriter<UndoEvent>,
+    mut ev_redo: EventWriter<RedoEvent>,
 ) {
+    // Undo with Ctrl+Z and redo with Ctrl+Shift+Z, except while typing in a text field
+    if key_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight])
+        && key_input.just_pressed(KeyCode::Z)
+        && !window_query
+            .get_single()
+            .is_ok_and(|egui_context| egui_context.clone().get_mut().wants_keyboard_input())
+    {
+        if key_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+            ev_redo.send(RedoEvent);
+        } else {
+            ev_undo.send(UndoEvent);
+        }
+    }
     for (mut pan_orbit, mut transform) in pan_orbit_query.iter_mut() {
         if key_input.pressed(KeyCode::ControlLeft) {
             // Jump focus point 1m using Ctrl+Shift + Arrows
@@ -0,0 +1,913 @@
+use bevy::prelude::*;
+use bevy_easings::*;
+use bevy_egui::EguiContext;
+use bevy_inspector_egui::egui;
+use bevy_window::PrimaryWindow;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+use crate::events::{DeselectIdentifierEvent, LayoutEvent};
+use crate::graph_index::GraphIndex;
+use crate::history::{Edit, History};
+use crate::identifiers::{Identifier, Pinned, SelectedIdentifier};
+use crate::jobs::{poll, Job, Jobs, Progress};
+use crate::resources::{Configuration, SeededRng};
+
+/// Stress layouts keep an all-pairs distance matrix, so they are limited to small graphs.
+const MAX_STRESS_NODES: usize = 2000;
+/// Fruchterman-Reingold compares every pair of identifiers in each iteration.
+const MAX_FRUCHTERMAN_REINGOLD_NODES: usize = 5000;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+pub enum LayoutAlgorithm {
+    #[default]
+    FruchtermanReingold,
+    KamadaKawai,
+    Spectral,
+    Layered,
+}
+
+impl LayoutAlgorithm {
+    pub const ALL: [LayoutAlgorithm; 4] = [
+        LayoutAlgorithm::FruchtermanReingold,
+        LayoutAlgorithm::KamadaKawai,
+        LayoutAlgorithm::Spectral,
+        LayoutAlgorithm::Layered,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LayoutAlgorithm::FruchtermanReingold => "Fruchterman-Reingold",
+            LayoutAlgorithm::KamadaKawai => "Kamada-Kawai",
+            LayoutAlgorithm::Spectral => "Spectral",
+            LayoutAlgorithm::Layered => "Layered (Sugiyama)",
+        }
+    }
+}
+
+#[derive(Reflect, Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[reflect(Resource)]
+#[serde(default)]
+pub struct LayoutSettings {
+    pub algorithm: LayoutAlgorithm,
+    /// 2 lays the graph out flat on the XY plane, 3 in space.
+    pub dimensions: usize,
+    pub iterations: u32,
+    /// Multiplier of the ideal edge length in Fruchterman-Reingold.
+    pub spacing: f32,
+}
+
+impl Default for LayoutSettings {
+    fn default() -> Self {
+        Self {
+            algorithm: LayoutAlgorithm::FruchtermanReingold,
+            dimensions: 3,
+            iterations: 100,
+            spacing: 1.0,
+        }
+    }
+}
+
+/// Arranges the graph around the selected identifier: it moves to the origin
+/// and everything else onto concentric spheres by hop count.
+#[derive(Resource, Default)]
+pub struct EgoLayout {
+    pub enabled: bool,
+    /// Positions from before the first arrangement, restored on deselect.
+    saved: Option<Vec<(Entity, Vec3)>>,
+}
+
+pub struct LayoutPlugin;
+
+impl Plugin for LayoutPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LayoutSettings>()
+            .register_type::<LayoutSettings>()
+            .init_resource::<EgoLayout>()
+            .add_systems(Update, apply_layout)
+            .add_systems(Update, update_ego_layout);
+    }
+}
+
+pub fn layout_ui(
+    query: Query<&mut EguiContext, With<PrimaryWindow>>,
+    mut settings: ResMut<LayoutSettings>,
+    mut ego_layout: ResMut<EgoLayout>,
+    mut ev: EventWriter<LayoutEvent>,
+) {
+    let mut egui_context = query.single().clone();
+
+    egui::Window::new("Layout")
+        .vscroll(false)
+        .hscroll(false)
+        .default_width(250.0)
+        .resizable(false)
+        .show(egui_context.get_mut(), |ui| {
+            egui::ComboBox::from_label("Algorithm")
+                .selected_text(settings.algorithm.name())
+                .show_ui(ui, |ui| {
+                    for algorithm in LayoutAlgorithm::ALL {
+                        ui.selectable_value(&mut settings.algorithm, algorithm, algorithm.name());
+                    }
+                });
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut settings.dimensions, 2, "2D");
+                ui.radio_value(&mut settings.dimensions, 3, "3D");
+            });
+            ui.add(egui::Slider::new(&mut settings.iterations, 1..=1000).text("Iterations"));
+            if settings.algorithm == LayoutAlgorithm::FruchtermanReingold {
+                ui.add(egui::Slider::new(&mut settings.spacing, 0.1..=5.0).text("Spacing"));
+            }
+            if ui.button("Apply").clicked() {
+                ev.send(LayoutEvent {
+                    settings: settings.clone(),
+                });
+            }
+
+            ui.separator();
+            let mut enabled = ego_layout.enabled;
+            if ui
+                .checkbox(&mut enabled, "Ego layout around selection")
+                .changed()
+            {
+                ego_layout.enabled = enabled;
+            }
+        });
+}
+
+/// Eases every identifier to its new position, keeping its scale. Pinned
+/// identifiers stay where they are. The move is recorded as one edit.
+pub fn ease_identifiers_to(
+    commands: &mut Commands,
+    history: &mut History,
+    configuration: &Configuration,
+    identifier_query: &Query<(&Transform, Has<Pinned>), With<Identifier>>,
+    positions: impl IntoIterator<Item = (Entity, Vec3)>,
+) {
+    let mut previous = vec![];
+    for (entity, position) in positions {
+        if let Ok((transform, false)) = identifier_query.get(entity) {
+            previous.push((entity, *transform));
+            commands.entity(entity).insert(transform.ease_to(
+                Transform::from_translation(position).with_scale(transform.scale),
+                EaseFunction::QuarticOut,
+                bevy_easings::EasingType::Once {
+                    duration: (std::time::Duration::from_secs(configuration.animation_duration)),
+                },
+            ));
+        }
+    }
+    if !previous.is_empty() {
+        history.record(Edit::Transforms(previous));
+    }
+}
+
+fn apply_layout(
+    mut commands: Commands,
+    mut ev: EventReader<LayoutEvent>,
+    mut job: Local<Option<Job<Vec<(Entity, Vec3)>>>>,
+    mut jobs: ResMut<Jobs>,
+    mut history: ResMut<History>,
+    configuration: Res<Configuration>,
+    mut seeded_rng: ResMut<SeededRng>,
+    graph_index: Res<GraphIndex>,
+    identifier_query: Query<(&Transform, Has<Pinned>), With<Identifier>>,
+) {
+    for e in ev.read() {
+        let n = graph_index.len();
+        if n == 0 {
+            continue;
+        }
+        let settings = e.settings.clone();
+        if settings.algorithm == LayoutAlgorithm::KamadaKawai && n > MAX_STRESS_NODES {
+            warn!(
+                "Kamada-Kawai is limited to {} identifiers, the graph has {}",
+                MAX_STRESS_NODES, n
+            );
+            continue;
+        }
+        if settings.algorithm == LayoutAlgorithm::FruchtermanReingold
+            && n > MAX_FRUCHTERMAN_REINGOLD_NODES
+        {
+            warn!(
+                "Fruchterman-Reingold is limited to {} identifiers, the graph has {}",
+                MAX_FRUCHTERMAN_REINGOLD_NODES, n
+            );
+            continue;
+        }
+        let edges = graph_index.edges();
+        let entities: Vec<Entity> = (0..n).map(|i| graph_index.entity(i)).collect();
+        let (start, pinned): (Vec<Vec3>, Vec<bool>) = entities
+            .iter()
+            .map(|&entity| {
+                identifier_query
+                    .get(entity)
+                    .map_or((Vec3::ZERO, false), |(transform, pinned)| {
+                        (transform.translation, pinned)
+                    })
+            })
+            .unzip();
+        // Pinned identifiers stay put, so the force layouts work around them in
+        // world coordinates instead of being moved and scaled afterwards.
+        let any_pinned = pinned.contains(&true);
+        // The job draws from its own generator, seeded from the shared one so
+        // runs stay repeatable.
+        let mut rng = StdRng::seed_from_u64(seeded_rng.rng().gen());
+        let container_size = configuration.container_size;
+
+        // Replacing a running job cancels it.
+        *job = Some(jobs.spawn(settings.algorithm.name(), move |progress| {
+            let rng = &mut rng;
+            let positions = match settings.algorithm {
+                LayoutAlgorithm::FruchtermanReingold => fruchterman_reingold(
+                    rng,
+                    start,
+                    &pinned,
+                    &edges,
+                    settings.dimensions,
+                    settings.iterations,
+                    settings.spacing * container_size / (n as f32).cbrt(),
+                    progress,
+                ),
+                LayoutAlgorithm::KamadaKawai => kamada_kawai(
+                    rng,
+                    start,
+                    &pinned,
+                    &edges,
+                    settings.dimensions,
+                    settings.iterations,
+                    container_size,
+                    progress,
+                ),
+                LayoutAlgorithm::Spectral => spectral(
+                    rng,
+                    n,
+                    &edges,
+                    settings.dimensions,
+                    settings.iterations,
+                    progress,
+                ),
+                LayoutAlgorithm::Layered => layered(
+                    n,
+                    &edges,
+                    settings.dimensions,
+                    settings.iterations,
+                    progress,
+                ),
+            };
+
+            let positions = match settings.algorithm {
+                LayoutAlgorithm::FruchtermanReingold | LayoutAlgorithm::KamadaKawai
+                    if any_pinned =>
+                {
+                    positions
+                }
+                // Fruchterman-Reingold already has the size the spacing asks for.
+                LayoutAlgorithm::FruchtermanReingold => centered(positions),
+                _ => fit_to_container(positions, container_size),
+            };
+            entities.into_iter().zip(positions).collect()
+        }));
+    }
+
+    if let Some(positions) = poll(&mut job) {
+        ease_identifiers_to(
+            &mut commands,
+            &mut history,
+            &configuration,
+            &identifier_query,
+            positions,
+        );
+    }
+}
+
+fn update_ego_layout(
+    mut commands: Commands,
+    mut ego_layout: ResMut<EgoLayout>,
+    mut ev_deselect: EventReader<DeselectIdentifierEvent>,
+    selected_identifier: Res<SelectedIdentifier>,
+    mut history: ResMut<History>,
+    configuration: Res<Configuration>,
+    graph_index: Res<GraphIndex>,
+    identifier_query: Query<(&Transform, Has<Pinned>), With<Identifier>>,
+) {
+    let deselected = ev_deselect.read().count() > 0;
+    if deselected || !ego_layout.enabled {
+        if let Some(saved) = ego_layout.saved.take() {
+            ease_identifiers_to(
+                &mut commands,
+                &mut history,
+                &configuration,
+                &identifier_query,
+                saved,
+            );
+        }
+        return;
+    }
+    if !selected_identifier.is_changed() && !ego_layout.is_changed() {
+        return;
+    }
+    let Some(center) = selected_identifier
+        .0
+        .and_then(|entity| graph_index.index_of(entity))
+    else {
+        return;
+    };
+
+    let n = graph_index.len();
+    let entities = (0..n).map(|i| graph_index.entity(i));
+    if ego_layout.saved.is_none() {
+        ego_layout.saved = Some(
+            entities
+                .clone()
+                .filter_map(|entity| {
+                    Some((entity, identifier_query.get(entity).ok()?.0.translation))
+                })
+                .collect(),
+        );
+    }
+    let positions = ego(
+        n,
+        &graph_index.edges(),
+        center,
+        configuration.container_size,
+    );
+    ease_identifiers_to(
+        &mut commands,
+        &mut history,
+        &configuration,
+        &identifier_query,
+        entities.zip(positions),
+    );
+}
+
+fn flatten(position: Vec3, dimensions: usize) -> Vec3 {
+    if dimensions == 2 {
+        position * Vec3::new(1.0, 1.0, 0.0)
+    } else {
+        position
+    }
+}
+
+/// Moves coincident points apart, as forces between them have no direction.
+/// Pinned points stay where they are.
+fn jitter(rng: &mut impl Rng, positions: &mut [Vec3], pinned: &[bool], dimensions: usize) {
+    for (position, &pinned) in positions.iter_mut().zip(pinned) {
+        if pinned {
+            continue;
+        }
+        let offset = Vec3::new(
+            rng.gen_range(-0.01..0.01),
+            rng.gen_range(-0.01..0.01),
+            rng.gen_range(-0.01..0.01),
+        );
+        *position = flatten(*position + offset, dimensions);
+    }
+}
+
+/// Fruchterman and Reingold, "Graph Drawing by Force-directed Placement" (1991).
+/// Every pair of identifiers repels with `k²/d`, connected ones attract with
+/// `d²/k`, and moves are capped by a temperature that cools linearly. Pinned
+/// identifiers push and pull the others but do not move.
+fn fruchterman_reingold(
+    rng: &mut impl Rng,
+    mut positions: Vec<Vec3>,
+    pinned: &[bool],
+    edges: &[(usize, usize)],
+    dimensions: usize,
+    iterations: u32,
+    k: f32,
+    progress: &Progress,
+) -> Vec<Vec3> {
+    let n = positions.len();
+    jitter(rng, &mut positions, pinned, dimensions);
+    let start_temperature = 10.0 * k;
+    let mut displacement = vec![Vec3::ZERO; n];
+
+    for iteration in 0..iterations {
+        if progress.is_cancelled() {
+            break;
+        }
+        progress.set(iteration as f32 / iterations as f32);
+        displacement.iter_mut().for_each(|d| *d = Vec3::ZERO);
+        for i in 0..n {
+            for j in (i + 1)..n {
+                let delta = positions[i] - positions[j];
+                let distance = delta.length().max(0.001);
+                let force = delta / distance * (k * k / distance);
+                displacement[i] += force;
+                displacement[j] -= force;
+            }
+        }
+        for &(i, j) in edges {
+            let delta = positions[i] - positions[j];
+            let distance = delta.length().max(0.001);
+            let force = delta / distance * (distance * distance / k);
+            displacement[i] -= force;
+            displacement[j] += force;
+        }
+
+        let temperature = start_temperature * (1.0 - iteration as f32 / iterations as f32);
+        for ((position, d), &pinned) in positions.iter_mut().zip(displacement.iter()).zip(pinned) {
+            let length = d.length();
+            if length > 0.0 && !pinned {
+                *position += *d / length * length.min(temperature);
+            }
+        }
+    }
+    positions
+}
+
+fn neighbours(n: usize, edges: &[(usize, usize)]) -> Vec<Vec<usize>> {
+    let mut neighbours = vec![vec![]; n];
+    for &(i, j) in edges {
+        neighbours[i].push(j);
+        neighbours[j].push(i);
+    }
+    neighbours
+}
+
+/// Breadth-first search from `source`, ignoring edge direction. Every reached
+/// identifier gets its hop count and the identifier it was reached from.
+fn breadth_first(neighbours: &[Vec<usize>], source: usize) -> Vec<Option<(u32, usize)>> {
+    let mut reached = vec![None; neighbours.len()];
+    reached[source] = Some((0, source));
+    let mut queue = VecDeque::from([source]);
+    while let Some(node) = queue.pop_front() {
+        let distance = reached[node].map_or(0, |(distance, _)| distance);
+        for &next in neighbours[node].iter() {
+            if reached[next].is_none() {
+                reached[next] = Some((distance + 1, node));
+                queue.push_back(next);
+            }
+        }
+    }
+    reached
+}
+
+/// Breadth-first hop counts from every identifier. Unreachable pairs are `None`.
+fn all_pairs_distances(n: usize, edges: &[(usize, usize)]) -> Vec<Vec<Option<u32>>> {
+    let neighbours = neighbours(n, edges);
+    (0..n)
+        .map(|source| {
+            breadth_first(&neighbours, source)
+                .into_iter()
+                .map(|reached| reached.map(|(distance, _)| distance))
+                .collect()
+        })
+        .collect()
+}
+
+/// Kamada and Kawai's spring model, minimised by stress majorization
+/// (Gansner, Koren and North, 2004). Ideal distances are hop counts;
+/// disconnected parts are kept one hop further apart than the graph's diameter.
+/// A hop is as long as the graph's radius fits `container_size`. Pinned
+/// identifiers stay where they are.
+fn kamada_kawai(
+    rng: &mut impl Rng,
+    mut positions: Vec<Vec3>,
+    pinned: &[bool],
+    edges: &[(usize, usize)],
+    dimensions: usize,
+    iterations: u32,
+    container_size: f32,
+    progress: &Progress,
+) -> Vec<Vec3> {
+    let n = positions.len();
+    jitter(rng, &mut positions, pinned, dimensions);
+    let hops = all_pairs_distances(n, edges);
+    let diameter = hops.iter().flatten().flatten().copied().max().unwrap_or(0);
+    let hop = 2.0 * container_size / (diameter + 1) as f32;
+    let distance = |i: usize, j: usize| hops[i][j].unwrap_or(diameter + 1) as f32 * hop;
+
+    for iteration in 0..iterations {
+        if progress.is_cancelled() {
+            break;
+        }
+        progress.set(iteration as f32 / iterations as f32);
+        for i in 0..n {
+            if pinned[i] {
+                continue;
+            }
+            let mut numerator = Vec3::ZERO;
+            let mut denominator = 0.0;
+            for j in 0..n {
+                if i == j {
+                    continue;
+                }
+                let d = distance(i, j);
+                let weight = 1.0 / (d * d);
+                let delta = positions[i] - positions[j];
+                let length = delta.length().max(0.001);
+                numerator += weight * (positions[j] + d * delta / length);
+                denominator += weight;
+            }
+            if denominator > 0.0 {
+                positions[i] = flatten(numerator / denominator, dimensions);
+            }
+        }
+    }
+    positions
+}
+
+/// Places identifiers by the eigenvectors of the graph Laplacian with the
+/// smallest non-zero eigenvalues, found by power iteration on `c·I - L`.
+fn spectral(
+    rng: &mut impl Rng,
+    n: usize,
+    edges: &[(usize, usize)],
+    dimensions: usize,
+    iterations: u32,
+    progress: &Progress,
+) -> Vec<Vec3> {
+    let mut neighbours = vec![vec![]; n];
+    for &(i, j) in edges {
+        if i != j {
+            neighbours[i].push(j);
+            neighbours[j].push(i);
+        }
+    }
+    let max_degree = neighbours.iter().map(Vec::len).max().unwrap_or(0);
+    // Bounds the largest Laplacian eigenvalue, so `c·I - L` is positive semi-definite.
+    let shift = 2.0 * max_degree as f64 + 1.0;
+
+    let constant = vec![1.0 / (n as f64).sqrt(); n];
+    let mut found: Vec<Vec<f64>> = vec![constant];
+
+    let steps = iterations.max(1);
+    for dimension in 0..dimensions as u32 {
+        let mut vector: Vec<f64> = (0..n).map(|_| rng.gen_range(-1.0..1.0)).collect();
+        orthonormalize(&mut vector, &found);
+        for step in 0..steps {
+            if progress.is_cancelled() {
+                break;
+            }
+            progress.set((dimension * steps + step) as f32 / (dimensions as u32 * steps) as f32);
+            let mut next: Vec<f64> = (0..n)
+                .map(|i| {
+                    let laplacian = neighbours[i].len() as f64 * vector[i]
+                        - neighbours[i].iter().map(|&j| vector[j]).sum::<f64>();
+                    shift * vector[i] - laplacian
+                })
+                .collect();
+            orthonormalize(&mut next, &found);
+            vector = next;
+        }
+        found.push(vector);
+    }
+
+    (0..n)
+        .map(|i| {
+            let coordinate = |d: usize| found.get(d + 1).map_or(0.0, |v| v[i] as f32);
+            Vec3::new(coordinate(0), coordinate(1), coordinate(2))
+        })
+        .collect()
+}
+
+/// Removes the components along `basis` (orthonormal vectors) and normalizes.
+fn orthonormalize(vector: &mut [f64], basis: &[Vec<f64>]) {
+    for b in basis {
+        let dot: f64 = vector.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
+        vector
+            .iter_mut()
+            .zip(b.iter())
+            .for_each(|(x, y)| *x -= dot * y);
+    }
+    let norm = vector.iter().map(|x| x * x).sum::<f64>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
+/// Sugiyama-style layered layout for DAG-like graphs such as credential chains:
+/// issuers on top, the identifiers they issued to below. Cycles are broken by
+/// reversing DFS back edges, identifiers are layered by the longest path from a
+/// source, and `sweeps` rounds of the barycenter heuristic order each layer to
+/// reduce crossings. Layers are stacked down the Y axis; in 3D a wide layer is
+/// wrapped into a grid on its XZ plane.
+fn layered(
+    n: usize,
+    edges: &[(usize, usize)],
+    dimensions: usize,
+    sweeps: u32,
+    progress: &Progress,
+) -> Vec<Vec3> {
+    let edges = acyclic_edges(n, edges);
+
+    // Longest path layering in topological order.
+    let mut successors = vec![vec![]; n];
+    let mut in_degree = vec![0; n];
+    for &(from, to) in edges.iter() {
+        successors[from].push(to);
+        in_degree[to] += 1;
+    }
+    let mut layer = vec![0; n];
+    let mut queue: VecDeque<usize> = (0..n).filter(|&v| in_degree[v] == 0).collect();
+    while let Some(v) = queue.pop_front() {
+        for &w in successors[v].iter() {
+            layer[w] = layer[w].max(layer[v] + 1);
+            in_degree[w] -= 1;
+            if in_degree[w] == 0 {
+                queue.push_back(w);
+            }
+        }
+    }
+
+    // Edges spanning several layers go through one dummy node per layer in between.
+    let layer_count = layer.iter().max().map_or(0, |max| max + 1);
+    let mut layers: Vec<Vec<usize>> = vec![vec![]; layer_count];
+    for (v, &l) in layer.iter().enumerate() {
+        layers[l].push(v);
+    }
+    let mut up: Vec<Vec<usize>> = vec![vec![]; n];
+    let mut down: Vec<Vec<usize>> = vec![vec![]; n];
+    for &(from, to) in edges.iter() {
+        let mut previous = from;
+        for layer_nodes in layers[(layer[from] + 1)..layer[to]].iter_mut() {
+            let dummy = up.len();
+            up.push(vec![]);
+            down.push(vec![]);
+            layer_nodes.push(dummy);
+            down[previous].push(dummy);
+            up[dummy].push(previous);
+            previous = dummy;
+        }
+        down[previous].push(to);
+        up[to].push(previous);
+    }
+
+    let mut best = layers.clone();
+    let mut best_crossings = crossings(&layers, &down, up.len());
+    for sweep in 0..sweeps {
+        if best_crossings == 0 || progress.is_cancelled() {
+            break;
+        }
+        progress.set(sweep as f32 / sweeps as f32);
+        if sweep % 2 == 0 {
+            for l in 1..layers.len() {
+                order_by_barycenter(&mut layers, l, l - 1, &up, up.len());
+            }
+        } else {
+            for l in (0..layers.len().saturating_sub(1)).rev() {
+                order_by_barycenter(&mut layers, l, l + 1, &down, up.len());
+            }
+        }
+        let count = crossings(&layers, &down, up.len());
+        if count < best_crossings {
+            best_crossings = count;
+            best = layers.clone();
+        }
+    }
+
+    // Dummy nodes keep their slot, so long edges do not cut through identifiers.
+    let widest = best.iter().map(Vec::len).max().unwrap_or(1);
+    let columns = if dimensions == 3 {
+        (widest as f32).sqrt().ceil() as usize
+    } else {
+        widest
+    };
+    let layer_spacing = (columns as f32 / layer_count.max(1) as f32).max(1.0);
+    let mut positions = vec![Vec3::ZERO; n];
+    for (l, nodes) in best.iter().enumerate() {
+        let columns = columns.min(nodes.len()).max(1);
+        let rows = nodes.len().div_ceil(columns);
+        for (i, &v) in nodes.iter().enumerate() {
+            if v < n {
+                positions[v] = Vec3::new(
+                    (i % columns) as f32 - (columns - 1) as f32 / 2.0,
+                    -(l as f32) * layer_spacing,
+                    (i / columns) as f32 - (rows - 1) as f32 / 2.0,
+                );
+            }
+        }
+    }
+    positions
+}
+
+/// `edges` without self-loops and with the back edges of a depth-first search reversed.
+fn acyclic_edges(n: usize, edges: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let mut successors = vec![vec![]; n];
+    for &(from, to) in edges {
+        if from != to {
+            successors[from].push(to);
+        }
+    }
+    // 0 unvisited, 1 on the DFS stack, 2 done
+    let mut state = vec![0u8; n];
+    let mut result = Vec::with_capacity(edges.len());
+    for root in 0..n {
+        if state[root] != 0 {
+            continue;
+        }
+        state[root] = 1;
+        let mut stack = vec![(root, 0)];
+        while let Some((v, next)) = stack.last_mut() {
+            let v = *v;
+            if let Some(&w) = successors[v].get(*next) {
+                *next += 1;
+                match state[w] {
+                    0 => {
+                        result.push((v, w));
+                        state[w] = 1;
+                        stack.push((w, 0));
+                    }
+                    1 => result.push((w, v)),
+                    _ => result.push((v, w)),
+                }
+            } else {
+                state[v] = 2;
+                stack.pop();
+            }
+        }
+    }
+    result
+}
+
+/// Reorders layer `l` by the mean position of each node's neighbours in layer
+/// `fixed`. Nodes without neighbours there keep their position.
+fn order_by_barycenter(
+    layers: &mut [Vec<usize>],
+    l: usize,
+    fixed: usize,
+    neighbours: &[Vec<usize>],
+    node_count: usize,
+) {
+    let mut position = vec![0.0; node_count];
+    for (i, &v) in layers[fixed].iter().enumerate() {
+        position[v] = i as f32;
+    }
+    let mut keyed: Vec<(f32, usize)> = layers[l]
+        .iter()
+        .enumerate()
+        .map(|(i, &v)| {
+            let adjacent = neighbours.get(v).map_or(&[][..], Vec::as_slice);
+            if adjacent.is_empty() {
+                (i as f32, v)
+            } else {
+                let sum: f32 = adjacent.iter().map(|&w| position[w]).sum();
+                (sum / adjacent.len() as f32, v)
+            }
+        })
+        .collect();
+    keyed.sort_by(|a, b| a.0.total_cmp(&b.0));
+    layers[l] = keyed.into_iter().map(|(_, v)| v).collect();
+}
+
+/// Number of edge crossings between consecutive layers, counted as inversions.
+fn crossings(layers: &[Vec<usize>], down: &[Vec<usize>], node_count: usize) -> usize {
+    let mut position = vec![0; node_count];
+    for nodes in layers.iter() {
+        for (i, &v) in nodes.iter().enumerate() {
+            position[v] = i;
+        }
+    }
+    let mut total = 0;
+    for pair in layers.windows(2) {
+        let mut ends: Vec<(usize, usize)> = pair[0]
+            .iter()
+            .flat_map(|&v| down[v].iter().map(move |&w| (v, w)))
+            .map(|(v, w)| (position[v], position[w]))
+            .collect();
+        ends.sort_unstable();
+        // Fenwick tree over positions in the lower layer.
+        let size = pair[1].len() + 1;
+        let mut tree = vec![0usize; size + 1];
+        for (seen, &(_, lower)) in ends.iter().enumerate() {
+            let mut not_greater = 0;
+            let mut i = lower + 1;
+            while i > 0 {
+                not_greater += tree[i];
+                i -= i & i.wrapping_neg();
+            }
+            total += seen - not_greater;
+            let mut i = lower + 1;
+            while i <= size {
+                tree[i] += 1;
+                i += i & i.wrapping_neg();
+            }
+        }
+    }
+    total
+}
+
+/// Puts `center` at the origin and every other identifier on the sphere of its
+/// hop count, the outermost of radius `radius`. Unreachable identifiers get a
+/// sphere of their own outside the rest. Each shell is filled in the order of
+/// the shell inside it, so identifiers reached from the same one end up close.
+fn ego(n: usize, edges: &[(usize, usize)], center: usize, radius: f32) -> Vec<Vec3> {
+    let reached = breadth_first(&neighbours(n, edges), center);
+    let unreachable = reached
+        .iter()
+        .flatten()
+        .map(|&(distance, _)| distance + 1)
+        .max()
+        .unwrap_or(1);
+    let mut shells = vec![vec![]; unreachable as usize + 1];
+    for (node, reached) in reached.iter().enumerate() {
+        let shell = reached.map_or(unreachable, |(distance, _)| distance);
+        shells[shell as usize].push(node);
+    }
+    // An empty outer shell would only push the rest inwards.
+    if shells.last().is_some_and(Vec::is_empty) {
+        shells.pop();
+    }
+
+    let mut positions = vec![Vec3::ZERO; n];
+    let mut slot = vec![0; n];
+    let shell_count = shells.len().saturating_sub(1).max(1);
+    for (distance, shell) in shells.iter_mut().enumerate().skip(1) {
+        shell.sort_by_key(|&node| reached[node].map_or(0, |(_, parent)| slot[parent]));
+        let shell_radius = radius * distance as f32 / shell_count as f32;
+        for (i, &node) in shell.iter().enumerate() {
+            slot[node] = i;
+            positions[node] = fibonacci_sphere(i, shell.len()) * shell_radius;
+        }
+    }
+    positions
+}
+
+/// Point `i` of `count` spread evenly over the unit sphere along a spiral, so
+/// consecutive points are neighbours.
+fn fibonacci_sphere(i: usize, count: usize) -> Vec3 {
+    let y = 1.0 - 2.0 * (i as f32 + 0.5) / count as f32;
+    let ring = (1.0 - y * y).sqrt();
+    let direction = golden_angle_direction(i);
+    Vec3::new(ring * direction.x, y, ring * direction.y)
+}
+
+/// The direction of the `i`th point of a golden-angle spiral, which spreads
+/// points evenly however many there are.
+pub fn golden_angle_direction(i: usize) -> Vec2 {
+    let golden_angle = std::f32::consts::PI * (3.0 - 5f32.sqrt());
+    Vec2::from_angle(golden_angle * i as f32)
+}
+
+fn centered(mut positions: Vec<Vec3>) -> Vec<Vec3> {
+    let center = positions.iter().copied().sum::<Vec3>() / positions.len().max(1) as f32;
+    positions
+        .iter_mut()
+        .for_each(|position| *position -= center);
+    positions
+}
+
+/// Centres the layout on the origin and scales it to fill the container.
+fn fit_to_container(positions: Vec<Vec3>, container_size: f32) -> Vec<Vec3> {
+    let mut positions = centered(positions);
+    let radius = positions
+        .iter()
+        .map(|position| position.length())
+        .fold(0.0, f32::max);
+    if radius > 0.0 {
+        let scale = container_size / radius;
+        positions.iter_mut().for_each(|position| *position *= scale);
+    }
+    positions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(n: usize, edges: &[(usize, usize)]) -> Vec<Vec3> {
+        layered(n, edges, 2, 8, &Progress::new("Layout"))
+    }
+
+    #[test]
+    fn layered_puts_each_target_below_its_source() {
+        let edges = [(0, 1), (1, 2), (0, 2), (3, 2)];
+        let positions = layout(4, &edges);
+        for (from, to) in edges {
+            assert!(positions[from].y > positions[to].y, "{from} -> {to}");
+        }
+        // Longest path: 0 and 3 are sources, 2 sits below 1 rather than beside it.
+        assert_eq!(positions[0].y, positions[3].y);
+        assert!(positions[1].y > positions[2].y);
+    }
+
+    #[test]
+    fn layered_breaks_cycles() {
+        let edges = [(0, 1), (1, 2), (2, 0), (1, 1)];
+        let positions = layout(3, &edges);
+        assert!(positions.iter().all(|position| position.is_finite()));
+        for (i, a) in positions.iter().enumerate() {
+            for b in positions[i + 1..].iter() {
+                assert_ne!(a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn layered_untangles_crossings() {
+        let layers = vec![vec![0, 1], vec![2, 3]];
+        let down = vec![vec![3], vec![2], vec![], vec![]];
+        assert_eq!(crossings(&layers, &down, 4), 1);
+
+        let positions = layout(4, &[(0, 3), (1, 2)]);
+        assert_eq!(
+            positions[0].x < positions[1].x,
+            positions[3].x < positions[2].x
+        );
+    }
+}
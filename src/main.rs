@@ -9,10 +9,25 @@ use bevy_inspector_egui::DefaultInspectorConfigPlugin;
 use bevy_mod_picking::DefaultPickingPlugins;
 use bevy_panorbit_camera::*;
 mod assets;
+mod cli;
+mod communities;
+mod connectivity;
+mod edit;
 mod events;
+mod formats;
+mod graph_index;
+mod history;
 mod identifiers;
+mod inspector;
+mod jobs;
 mod keyboard;
+mod layout;
+mod metrics;
+mod paths;
 mod resources;
+mod search;
+mod selection;
+mod session;
 mod simulation;
 mod touch;
 mod ui;
@@ -20,17 +35,36 @@ mod util;
 
 use assets::AssetsPlugin;
 use bevy_window::PresentMode;
+use cli::CliArgs;
+use communities::CommunitiesPlugin;
+use connectivity::ConnectivityPlugin;
+use edit::EditPlugin;
 use events::EventsPlugin;
+use formats::FormatsPlugin;
+use graph_index::GraphIndexPlugin;
+use history::HistoryPlugin;
 use identifiers::IdentifiersPlugin;
+use jobs::JobsPlugin;
 use keyboard::KeyboardPlugin;
-use resources::Configuration;
+use layout::LayoutPlugin;
+use metrics::MetricsPlugin;
+use paths::PathsPlugin;
+use resources::{Configuration, SeededRng};
+use search::SearchPlugin;
+use selection::SelectionPlugin;
+use session::SessionPlugin;
 use simulation::SimulationPlugin;
 use touch::TouchCameraPlugin;
 use ui::UiPlugin;
 
 fn main() {
+    let args = CliArgs::from_env();
+    let seed = args.seed.unwrap_or_else(rand::random);
+
     App::new()
-        .init_resource::<Configuration>()
+        .insert_resource(args)
+        .insert_resource(Configuration { seed, ..default() })
+        .insert_resource(SeededRng::new(seed))
         .register_type::<Configuration>()
         .add_plugins(DefaultPlugins.set(WindowPlugin {
             primary_window: Some(Window {
@@ -45,6 +79,8 @@ fn main() {
         .add_plugins(AssetsPlugin)
         .add_plugins(EventsPlugin)
         .add_plugins(IdentifiersPlugin)
+        .add_plugins(GraphIndexPlugin)
+        .add_plugins(JobsPlugin)
         .add_plugins(EguiPlugin)
         .add_plugins(DefaultInspectorConfigPlugin)
         .add_plugins(PanOrbitCameraPlugin)
@@ -53,6 +89,17 @@ fn main() {
         .add_plugins(KeyboardPlugin)
         .add_plugins(WorldInspectorPlugin::default().run_if(input_toggle_active(false, KeyCode::W)))
         .add_plugins(SimulationPlugin)
+        .add_plugins(LayoutPlugin)
+        .add_plugins(MetricsPlugin)
+        .add_plugins(CommunitiesPlugin)
+        .add_plugins(ConnectivityPlugin)
+        .add_plugins(PathsPlugin)
+        .add_plugins(SelectionPlugin)
+        .add_plugins(SearchPlugin)
+        .add_plugins(EditPlugin)
+        .add_plugins(HistoryPlugin)
+        .add_plugins(SessionPlugin)
+        .add_plugins(FormatsPlugin)
         .add_systems(Startup, setup)
         .run();
 }
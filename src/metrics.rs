@@ -0,0 +1,494 @@
+use bevy::prelude::*;
+use bevy_easings::*;
+use bevy_egui::EguiContext;
+use bevy_inspector_egui::egui;
+use bevy_window::PrimaryWindow;
+use graph::page_rank::PageRankConfig;
+use graph::prelude::*;
+use std::collections::VecDeque;
+
+use crate::events::{MetricsEvent, SizeByMetricEvent};
+use crate::graph_index::GraphIndex;
+use crate::history::{Edit, History};
+use crate::identifiers::{Identifier, SelectedIdentifier};
+use crate::jobs::{poll, Job, Jobs, Progress};
+use crate::resources::Configuration;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum Metric {
+    #[default]
+    PageRank,
+    Degree,
+    InDegree,
+    OutDegree,
+    Betweenness,
+    Closeness,
+    Eigenvector,
+    Hub,
+    Authority,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 9] = [
+        Metric::PageRank,
+        Metric::Degree,
+        Metric::InDegree,
+        Metric::OutDegree,
+        Metric::Betweenness,
+        Metric::Closeness,
+        Metric::Eigenvector,
+        Metric::Hub,
+        Metric::Authority,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Metric::PageRank => "PageRank",
+            Metric::Degree => "Degree",
+            Metric::InDegree => "In-degree",
+            Metric::OutDegree => "Out-degree",
+            Metric::Betweenness => "Betweenness",
+            Metric::Closeness => "Closeness",
+            Metric::Eigenvector => "Eigenvector",
+            Metric::Hub => "Hub",
+            Metric::Authority => "Authority",
+        }
+    }
+}
+
+/// Centralities of an identifier, as of the last time metrics were computed.
+#[derive(Component, Reflect, Clone, Debug, Default)]
+#[reflect(Component)]
+pub struct Centralities {
+    pub page_rank: f32,
+    pub degree: f32,
+    pub in_degree: f32,
+    pub out_degree: f32,
+    pub betweenness: f32,
+    pub closeness: f32,
+    pub eigenvector: f32,
+    pub hub: f32,
+    pub authority: f32,
+}
+
+impl Centralities {
+    pub fn get(&self, metric: Metric) -> f32 {
+        match metric {
+            Metric::PageRank => self.page_rank,
+            Metric::Degree => self.degree,
+            Metric::InDegree => self.in_degree,
+            Metric::OutDegree => self.out_degree,
+            Metric::Betweenness => self.betweenness,
+            Metric::Closeness => self.closeness,
+            Metric::Eigenvector => self.eigenvector,
+            Metric::Hub => self.hub,
+            Metric::Authority => self.authority,
+        }
+    }
+}
+
+pub struct MetricsPlugin;
+
+impl Plugin for MetricsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Centralities>()
+            .add_systems(Update, compute_metrics)
+            .add_systems(Update, size_by_metric);
+    }
+}
+
+pub struct MetricsForm {
+    iterations: usize,
+    tolerance: f64,
+    damping: f32,
+    size_by: Metric,
+}
+impl Default for MetricsForm {
+    fn default() -> Self {
+        Self {
+            iterations: 20,
+            tolerance: 1.0E-4f64,
+            damping: 0.8500f32,
+            size_by: Metric::PageRank,
+        }
+    }
+}
+
+pub fn metrics_ui(
+    query: Query<&mut EguiContext, With<PrimaryWindow>>,
+    mut form: Local<MetricsForm>,
+    selected_identifier: Res<SelectedIdentifier>,
+    centralities_query: Query<&Centralities>,
+    mut ev: EventWriter<MetricsEvent>,
+    mut ev_size: EventWriter<SizeByMetricEvent>,
+) {
+    let mut egui_context = query.single().clone();
+
+    egui::Window::new("Metrics")
+        .vscroll(false)
+        .hscroll(false)
+        .default_width(250.0)
+        .resizable(false)
+        .show(egui_context.get_mut(), |ui| {
+            ui.add(egui::Slider::new(&mut form.iterations, 1..=100).text("Iterations"));
+            ui.add(egui::Slider::new(&mut form.damping, 0.0..=2.0).text("Damping"));
+            ui.add(egui::Slider::new(&mut form.tolerance, 0.0..=0.001).text("Tolerance"));
+            egui::ComboBox::from_label("Size by")
+                .selected_text(form.size_by.name())
+                .show_ui(ui, |ui| {
+                    for metric in Metric::ALL {
+                        ui.selectable_value(&mut form.size_by, metric, metric.name());
+                    }
+                });
+            ui.horizontal(|ui| {
+                if ui.button("Compute").clicked() {
+                    ev.send(MetricsEvent {
+                        page_rank: PageRankConfig {
+                            max_iterations: form.iterations,
+                            tolerance: form.tolerance,
+                            damping_factor: form.damping,
+                        },
+                        size_by: form.size_by,
+                    });
+                }
+                if ui.button("Resize").clicked() {
+                    ev_size.send(SizeByMetricEvent(form.size_by));
+                }
+            });
+
+            let selected = selected_identifier
+                .0
+                .and_then(|entity| centralities_query.get(entity).ok());
+            if let Some(centralities) = selected {
+                ui.separator();
+                egui::Grid::new("centralities").show(ui, |ui| {
+                    for metric in Metric::ALL {
+                        ui.label(metric.name());
+                        ui.label(format!("{:.4}", centralities.get(metric)));
+                        ui.end_row();
+                    }
+                });
+            }
+        });
+}
+
+fn compute_metrics(
+    mut commands: Commands,
+    mut ev: EventReader<MetricsEvent>,
+    mut job: Local<Option<Job<(Metric, Vec<(Entity, Centralities)>)>>>,
+    mut jobs: ResMut<Jobs>,
+    mut history: ResMut<History>,
+    configuration: Res<Configuration>,
+    graph_index: Res<GraphIndex>,
+    identifier_query: Query<&Transform, With<Identifier>>,
+) {
+    for e in ev.read() {
+        let n = graph_index.len();
+        if n == 0 {
+            continue;
+        }
+        let edges = graph_index.edges();
+        let entities: Vec<Entity> = (0..n).map(|i| graph_index.entity(i)).collect();
+        let config = e.page_rank;
+        let size_by = e.size_by;
+        *job = Some(jobs.spawn("Metrics", move |progress| {
+            let graph: DirectedCsrGraph<usize> = GraphBuilder::new()
+                .edges(edges)
+                .node_values(vec![(); n])
+                .build();
+            let centralities = centralities(&graph, config, progress);
+            (size_by, entities.into_iter().zip(centralities).collect())
+        }));
+    }
+
+    let Some((size_by, centralities)) = poll(&mut job) else {
+        return;
+    };
+    ease_scales(
+        &mut commands,
+        &mut history,
+        &configuration,
+        &identifier_query,
+        centralities
+            .iter()
+            .map(|(entity, centralities)| (*entity, centralities.get(size_by))),
+    );
+    for (entity, centralities) in centralities {
+        if let Some(mut entity) = commands.get_entity(entity) {
+            entity.insert(centralities);
+        }
+    }
+}
+
+fn size_by_metric(
+    mut commands: Commands,
+    mut ev: EventReader<SizeByMetricEvent>,
+    mut history: ResMut<History>,
+    configuration: Res<Configuration>,
+    identifier_query: Query<&Transform, With<Identifier>>,
+    centralities_query: Query<(Entity, &Centralities)>,
+) {
+    for SizeByMetricEvent(metric) in ev.read() {
+        ease_scales(
+            &mut commands,
+            &mut history,
+            &configuration,
+            &identifier_query,
+            centralities_query
+                .iter()
+                .map(|(entity, centralities)| (entity, centralities.get(*metric))),
+        );
+    }
+}
+
+/// Eases every identifier to a scale by its value, from the smallest to the
+/// largest, as one edit.
+fn ease_scales(
+    commands: &mut Commands,
+    history: &mut History,
+    configuration: &Configuration,
+    identifier_query: &Query<&Transform, With<Identifier>>,
+    values: impl Iterator<Item = (Entity, f32)>,
+) {
+    let (entities, mut values): (Vec<Entity>, Vec<f32>) = values.unzip();
+    normalize(&mut values);
+    let mut previous = vec![];
+    for (entity, value) in entities.into_iter().zip(values) {
+        if let Ok(transform) = identifier_query.get(entity) {
+            previous.push((entity, *transform));
+            let pos = transform.translation;
+            commands.entity(entity).insert(
+                transform.ease_to(
+                    Transform::from_xyz(pos.x, pos.y, pos.z)
+                        .with_scale(Vec3::ONE * (value * 3.0 + 0.5)),
+                    EaseFunction::QuarticOut,
+                    bevy_easings::EasingType::Once {
+                        duration: (std::time::Duration::from_secs(
+                            configuration.animation_duration,
+                        )),
+                    },
+                ),
+            );
+        }
+    }
+    if !previous.is_empty() {
+        history.record(Edit::Transforms(previous));
+    }
+}
+
+fn normalize(vec: &mut [f32]) {
+    let min = vec.iter().cloned().fold(f32::INFINITY, f32::min);
+    let max = vec.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+
+    if max - min != 0.0 {
+        vec.iter_mut().for_each(|x| *x = (*x - min) / (max - min));
+    }
+}
+
+fn centralities(
+    graph: &DirectedCsrGraph<usize>,
+    config: PageRankConfig,
+    progress: &Progress,
+) -> Vec<Centralities> {
+    let n = graph.node_count();
+    let (page_rank, _, _) = page_rank(graph, config);
+    let (betweenness, closeness) = shortest_path_centralities(graph, progress);
+    let eigenvector = eigenvector(graph, config.max_iterations, config.tolerance);
+    let (hub, authority) = hits(graph, config.max_iterations, config.tolerance);
+
+    (0..n)
+        .map(|v| {
+            let in_degree = graph.in_degree(v) as f32;
+            let out_degree = graph.out_degree(v) as f32;
+            Centralities {
+                page_rank: page_rank[v],
+                degree: in_degree + out_degree,
+                in_degree,
+                out_degree,
+                betweenness: betweenness[v],
+                closeness: closeness[v],
+                eigenvector: eigenvector[v],
+                hub: hub[v],
+                authority: authority[v],
+            }
+        })
+        .collect()
+}
+
+/// Betweenness (Brandes, 2001) and closeness from one breadth-first search per
+/// identifier along connection direction. Closeness is scaled by the share of
+/// identifiers reached (Wasserman and Faust), so it stays comparable when the
+/// graph is not strongly connected.
+fn shortest_path_centralities(
+    graph: &DirectedCsrGraph<usize>,
+    progress: &Progress,
+) -> (Vec<f32>, Vec<f32>) {
+    let n = graph.node_count();
+    let mut betweenness = vec![0.0f64; n];
+    let mut closeness = vec![0.0f32; n];
+
+    let mut order = Vec::with_capacity(n);
+    let mut predecessors: Vec<Vec<usize>> = vec![vec![]; n];
+    let mut paths = vec![0.0f64; n];
+    let mut distance: Vec<Option<usize>> = vec![None; n];
+    let mut dependency = vec![0.0f64; n];
+    for source in 0..n {
+        if progress.is_cancelled() {
+            break;
+        }
+        progress.set(source as f32 / n as f32);
+
+        order.clear();
+        predecessors.iter_mut().for_each(Vec::clear);
+        paths.iter_mut().for_each(|p| *p = 0.0);
+        distance.iter_mut().for_each(|d| *d = None);
+        dependency.iter_mut().for_each(|d| *d = 0.0);
+        paths[source] = 1.0;
+        distance[source] = Some(0);
+
+        let mut queue = VecDeque::from([source]);
+        while let Some(v) = queue.pop_front() {
+            order.push(v);
+            let next = distance[v].unwrap_or(0) + 1;
+            for &w in graph.out_neighbors(v) {
+                if distance[w].is_none() {
+                    distance[w] = Some(next);
+                    queue.push_back(w);
+                }
+                if distance[w] == Some(next) {
+                    paths[w] += paths[v];
+                    predecessors[w].push(v);
+                }
+            }
+        }
+
+        let reached = order.len() - 1;
+        let total: usize = order.iter().filter_map(|&v| distance[v]).sum();
+        if total > 0 && n > 1 {
+            closeness[source] = (reached as f32 / total as f32) * (reached as f32 / (n - 1) as f32);
+        }
+
+        for &w in order.iter().rev() {
+            for &v in predecessors[w].iter() {
+                dependency[v] += paths[v] / paths[w] * (1.0 + dependency[w]);
+            }
+            if w != source {
+                betweenness[w] += dependency[w];
+            }
+        }
+    }
+    (
+        betweenness.into_iter().map(|b| b as f32).collect(),
+        closeness,
+    )
+}
+
+/// Eigenvector centrality by power iteration, ignoring connection direction.
+/// Iterating on `A + I` instead of `A` converges on bipartite graphs too.
+fn eigenvector(graph: &DirectedCsrGraph<usize>, iterations: usize, tolerance: f64) -> Vec<f32> {
+    let n = graph.node_count();
+    let mut x = vec![1.0 / (n as f64).sqrt(); n];
+    for _ in 0..iterations {
+        let mut next: Vec<f64> = (0..n)
+            .map(|v| {
+                x[v] + graph.out_neighbors(v).map(|&u| x[u]).sum::<f64>()
+                    + graph.in_neighbors(v).map(|&u| x[u]).sum::<f64>()
+            })
+            .collect();
+        normalize_l2(&mut next);
+        let change: f64 = next.iter().zip(x.iter()).map(|(a, b)| (a - b).abs()).sum();
+        x = next;
+        if change < tolerance {
+            break;
+        }
+    }
+    x.into_iter().map(|v| v as f32).collect()
+}
+
+/// Kleinberg's hubs and authorities: an identifier is a good authority when
+/// good hubs connect to it, and a good hub when it connects to good authorities.
+fn hits(
+    graph: &DirectedCsrGraph<usize>,
+    iterations: usize,
+    tolerance: f64,
+) -> (Vec<f32>, Vec<f32>) {
+    let n = graph.node_count();
+    let mut hubs = vec![1.0 / (n as f64).sqrt(); n];
+    let mut authorities = hubs.clone();
+    for _ in 0..iterations {
+        let mut next_authorities: Vec<f64> = (0..n)
+            .map(|v| graph.in_neighbors(v).fold(0.0, |sum, &u| sum + hubs[u]))
+            .collect();
+        normalize_l2(&mut next_authorities);
+        let mut next_hubs: Vec<f64> = (0..n)
+            .map(|v| {
+                graph
+                    .out_neighbors(v)
+                    .fold(0.0, |sum, &w| sum + next_authorities[w])
+            })
+            .collect();
+        normalize_l2(&mut next_hubs);
+
+        let change: f64 = next_hubs
+            .iter()
+            .zip(hubs.iter())
+            .map(|(a, b)| (a - b).abs())
+            .sum();
+        hubs = next_hubs;
+        authorities = next_authorities;
+        if change < tolerance {
+            break;
+        }
+    }
+    (
+        hubs.into_iter().map(|v| v as f32).collect(),
+        authorities.into_iter().map(|v| v as f32).collect(),
+    )
+}
+
+fn normalize_l2(vector: &mut [f64]) {
+    let norm = vector.iter().map(|x| x * x).sum::<f64>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(n: usize, edges: Vec<(usize, usize)>) -> DirectedCsrGraph<usize> {
+        GraphBuilder::new()
+            .edges(edges)
+            .node_values(vec![(); n])
+            .build()
+    }
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-5, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn directed_path() {
+        // 0 -> 1 -> 2
+        let (betweenness, closeness) =
+            shortest_path_centralities(&graph(3, vec![(0, 1), (1, 2)]), &Progress::new("test"));
+        assert_close(&betweenness, &[0.0, 1.0, 0.0]);
+        // 0 reaches both others at a total distance of 3, 1 reaches one of two.
+        assert_close(&closeness, &[2.0 / 3.0, 0.5, 0.0]);
+    }
+
+    #[test]
+    fn star() {
+        // Centre 0 connected both ways to 1, 2 and 3
+        let edges = (1..4).flat_map(|leaf| [(0, leaf), (leaf, 0)]).collect();
+        let (betweenness, closeness) =
+            shortest_path_centralities(&graph(4, edges), &Progress::new("test"));
+        // Every ordered pair of leaves goes through the centre.
+        assert_close(&betweenness, &[6.0, 0.0, 0.0, 0.0]);
+        assert_close(&closeness, &[1.0, 0.6, 0.6, 0.6]);
+    }
+}
@@ -0,0 +1,416 @@
+use bevy::prelude::*;
+use bevy_egui::EguiContext;
+use bevy_inspector_egui::egui;
+use bevy_window::PrimaryWindow;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, VecDeque};
+
+use crate::assets::MyAssets;
+use crate::events::DeselectTargetIdentifierEvent;
+use crate::graph_index::GraphIndex;
+use crate::identifiers::{
+    highlight_selection, update_identifiers_and_connections, Connection, Hidden, Identifier,
+    IdentifierMaterial, Neighbourhood, SelectedIdentifier, Selection, TargetIdentifier,
+};
+
+#[derive(Reflect, Resource, Default, Serialize, Deserialize)]
+#[reflect(Resource)]
+#[serde(default)]
+pub struct PathSettings {
+    /// Only follow connections from `from` to `to`.
+    pub directed: bool,
+}
+
+/// Shortest path from the selected identifier to the target one.
+#[derive(Resource, Default)]
+pub struct ShortestPath {
+    /// Identifiers along the path, from the selected one to the target.
+    pub identifiers: Vec<Entity>,
+    pub connections: Vec<Entity>,
+    /// Sum of connection weights, when the path was found by weight.
+    pub cost: Option<f32>,
+}
+
+pub struct PathsPlugin;
+
+impl Plugin for PathsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PathSettings>()
+            .register_type::<PathSettings>()
+            .init_resource::<ShortestPath>()
+            .add_systems(
+                Update,
+                highlight_shortest_path.after(update_identifiers_and_connections),
+            );
+    }
+}
+
+pub fn path_ui(
+    query: Query<&mut EguiContext, With<PrimaryWindow>>,
+    mut settings: ResMut<PathSettings>,
+    selected_identifier: Res<SelectedIdentifier>,
+    target_identifier: Res<TargetIdentifier>,
+    path: Res<ShortestPath>,
+    identifier_query: Query<&Identifier>,
+    mut ev_clear: EventWriter<DeselectTargetIdentifierEvent>,
+) {
+    let mut egui_context = query.single().clone();
+    let key = |entity: Option<Entity>| {
+        entity
+            .and_then(|entity| identifier_query.get(entity).ok())
+            .map_or("none".to_string(), |identifier| identifier.key.clone())
+    };
+
+    egui::Window::new("Path")
+        .vscroll(false)
+        .hscroll(false)
+        .default_width(250.0)
+        .resizable(false)
+        .show(egui_context.get_mut(), |ui| {
+            ui.label("Click an identifier, then ctrl-click another.");
+            ui.label(format!("From: {}", key(selected_identifier.0)));
+            ui.horizontal(|ui| {
+                ui.label(format!("To: {}", key(target_identifier.0)));
+                if target_identifier.0.is_some() && ui.small_button("Clear").clicked() {
+                    ev_clear.send(DeselectTargetIdentifierEvent);
+                }
+            });
+            let mut directed = settings.directed;
+            if ui
+                .checkbox(&mut directed, "Follow connection direction")
+                .changed()
+            {
+                settings.directed = directed;
+            }
+
+            if selected_identifier.0.is_none() || target_identifier.0.is_none() {
+                return;
+            }
+            ui.separator();
+            if path.identifiers.is_empty() {
+                ui.label("No path");
+            } else {
+                ui.label(format!("{} hops", path.connections.len()));
+                if let Some(cost) = path.cost {
+                    ui.label(format!("Total weight {:.3}", cost));
+                }
+            }
+        });
+}
+
+fn highlight_shortest_path(
+    mut commands: Commands,
+    my_assets: Res<MyAssets>,
+    selected_identifier: Res<SelectedIdentifier>,
+    selection: Res<Selection>,
+    neighbourhood: Res<Neighbourhood>,
+    target_identifier: Res<TargetIdentifier>,
+    settings: Res<PathSettings>,
+    mut path: ResMut<ShortestPath>,
+    graph_index: Res<GraphIndex>,
+    identifier_query: Query<(Entity, &Transform, Has<Hidden>), With<Identifier>>,
+    material_query: Query<&IdentifierMaterial>,
+    connection_query: Query<(Entity, &Connection), With<Connection>>,
+) {
+    if !selected_identifier.is_changed()
+        && !target_identifier.is_changed()
+        && !settings.is_changed()
+        && !neighbourhood.is_changed()
+    {
+        return;
+    }
+
+    let found = match (selected_identifier.0, target_identifier.0) {
+        (Some(from), Some(to)) => {
+            shortest_path(&graph_index, &connection_query, from, to, settings.directed)
+        }
+        _ => None,
+    };
+    let Some(found) = found else {
+        // Back to the plain selection highlight.
+        if !path.identifiers.is_empty() {
+            for (entity, _) in connection_query.iter() {
+                commands
+                    .entity(entity)
+                    .insert(my_assets.connection_material_handle.clone());
+            }
+            highlight_selection(
+                &mut commands,
+                &my_assets,
+                &selection.0,
+                neighbourhood.depth,
+                &identifier_query,
+                &material_query,
+                &connection_query,
+            );
+        }
+        *path = ShortestPath::default();
+        return;
+    };
+
+    for (entity, _, _) in identifier_query.iter() {
+        let material =
+            if Some(entity) == selected_identifier.0 || Some(entity) == target_identifier.0 {
+                &my_assets.identifier_selected_material_handle
+            } else if found.identifiers.contains(&entity) {
+                &my_assets.identifier_path_material_handle
+            } else {
+                &my_assets.identifier_dimmed_material_handle
+            };
+        commands.entity(entity).insert(material.clone());
+    }
+    let hidden = |entity| {
+        identifier_query
+            .get(entity)
+            .is_ok_and(|(_, _, hidden)| hidden)
+    };
+    for (entity, connection) in connection_query.iter() {
+        let material = if found.connections.contains(&entity) {
+            &my_assets.connection_path_material_handle
+        } else {
+            &my_assets.connection_dimmed_material_handle
+        };
+        let visibility = if hidden(connection.from) || hidden(connection.to) {
+            Visibility::Hidden
+        } else {
+            Visibility::Visible
+        };
+        commands
+            .entity(entity)
+            .insert((material.clone(), visibility));
+    }
+    *path = found;
+}
+
+/// Breadth-first search when every connection has the same weight, Dijkstra
+/// with weights as lengths otherwise.
+fn shortest_path(
+    graph_index: &GraphIndex,
+    connection_query: &Query<(Entity, &Connection), With<Connection>>,
+    from: Entity,
+    to: Entity,
+    directed: bool,
+) -> Option<ShortestPath> {
+    let source = graph_index.index_of(from)?;
+    let target = graph_index.index_of(to)?;
+
+    // (neighbour, length, connection)
+    let mut adjacency: Vec<Vec<(usize, f32, Entity)>> = vec![vec![]; graph_index.len()];
+    let mut weights = vec![];
+    for (entity, connection) in connection_query.iter() {
+        let (Some(a), Some(b)) = (
+            graph_index.index_of(connection.from),
+            graph_index.index_of(connection.to),
+        ) else {
+            continue;
+        };
+        let length = connection.weight.max(0.0);
+        adjacency[a].push((b, length, entity));
+        if !directed {
+            adjacency[b].push((a, length, entity));
+        }
+        weights.push(length);
+    }
+    let weighted = weights.windows(2).any(|pair| pair[0] != pair[1]);
+
+    let predecessors = if weighted {
+        dijkstra(&adjacency, source)
+    } else {
+        breadth_first(&adjacency, source)
+    };
+    if source != target && predecessors[target].is_none() {
+        return None;
+    }
+
+    let mut identifiers = vec![to];
+    let mut connections = vec![];
+    let mut cost = 0.0;
+    let mut node = target;
+    while node != source {
+        let (previous, length, connection) = predecessors[node]?;
+        identifiers.push(graph_index.entity(previous));
+        connections.push(connection);
+        cost += length;
+        node = previous;
+    }
+    identifiers.reverse();
+    connections.reverse();
+    Some(ShortestPath {
+        identifiers,
+        connections,
+        cost: weighted.then_some(cost),
+    })
+}
+
+type Predecessors = Vec<Option<(usize, f32, Entity)>>;
+
+fn breadth_first(adjacency: &[Vec<(usize, f32, Entity)>], source: usize) -> Predecessors {
+    let mut predecessors: Predecessors = vec![None; adjacency.len()];
+    let mut visited = vec![false; adjacency.len()];
+    visited[source] = true;
+    let mut queue = VecDeque::from([source]);
+    while let Some(node) = queue.pop_front() {
+        for &(next, length, connection) in adjacency[node].iter() {
+            if !visited[next] {
+                visited[next] = true;
+                predecessors[next] = Some((node, length, connection));
+                queue.push_back(next);
+            }
+        }
+    }
+    predecessors
+}
+
+/// Entry of the Dijkstra queue, ordered so the shortest distance comes first.
+#[derive(PartialEq)]
+struct Candidate {
+    distance: f32,
+    node: usize,
+}
+
+impl Eq for Candidate {}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.distance.total_cmp(&self.distance)
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn dijkstra(adjacency: &[Vec<(usize, f32, Entity)>], source: usize) -> Predecessors {
+    let mut predecessors: Predecessors = vec![None; adjacency.len()];
+    let mut distances = vec![f32::INFINITY; adjacency.len()];
+    distances[source] = 0.0;
+    let mut queue = BinaryHeap::from([Candidate {
+        distance: 0.0,
+        node: source,
+    }]);
+    while let Some(Candidate { distance, node }) = queue.pop() {
+        if distance > distances[node] {
+            continue;
+        }
+        for &(next, length, connection) in adjacency[node].iter() {
+            let candidate = distance + length;
+            if candidate < distances[next] {
+                distances[next] = candidate;
+                predecessors[next] = Some((node, length, connection));
+                queue.push(Candidate {
+                    distance: candidate,
+                    node: next,
+                });
+            }
+        }
+    }
+    predecessors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph_index::GraphIndexPlugin;
+    use crate::identifiers::ConnectionKind;
+    use bevy::ecs::system::SystemState;
+
+    /// A world with `n` indexed identifiers and `(from, to, weight)` connections between them.
+    fn graph(n: usize, connections: &[(usize, usize, f32)]) -> (App, Vec<Entity>, Vec<Entity>) {
+        let mut app = App::new();
+        app.add_plugins(GraphIndexPlugin);
+        let identifiers: Vec<Entity> = (0..n)
+            .map(|i| {
+                app.world
+                    .spawn(Identifier {
+                        key: i.to_string(),
+                        label: None,
+                    })
+                    .id()
+            })
+            .collect();
+        let connections = connections
+            .iter()
+            .map(|&(from, to, weight)| {
+                app.world
+                    .spawn(Connection {
+                        from: identifiers[from],
+                        to: identifiers[to],
+                        weight,
+                        kind: ConnectionKind::Generic,
+                    })
+                    .id()
+            })
+            .collect();
+        app.update();
+        (app, identifiers, connections)
+    }
+
+    fn find(app: &mut App, from: Entity, to: Entity, directed: bool) -> Option<ShortestPath> {
+        let mut state: SystemState<(
+            Res<GraphIndex>,
+            Query<(Entity, &Connection), With<Connection>>,
+        )> = SystemState::new(&mut app.world);
+        let (graph_index, connection_query) = state.get(&app.world);
+        shortest_path(&graph_index, &connection_query, from, to, directed)
+    }
+
+    #[test]
+    fn fewest_hops_when_unweighted() {
+        // 0 - 1 - 2 and 0 - 3 - 4 - 2
+        let (mut app, i, c) = graph(
+            5,
+            &[
+                (0, 1, 1.0),
+                (1, 2, 1.0),
+                (0, 3, 1.0),
+                (3, 4, 1.0),
+                (4, 2, 1.0),
+            ],
+        );
+        let path = find(&mut app, i[0], i[2], false).unwrap();
+        assert_eq!(path.identifiers, vec![i[0], i[1], i[2]]);
+        assert_eq!(path.connections, vec![c[0], c[1]]);
+        assert_eq!(path.cost, None);
+
+        let path = find(&mut app, i[3], i[3], false).unwrap();
+        assert_eq!(path.identifiers, vec![i[3]]);
+        assert!(path.connections.is_empty());
+    }
+
+    #[test]
+    fn lightest_when_weighted() {
+        // 0 -> 2 directly weighs 5, through 1 it weighs 2.
+        let (mut app, i, c) = graph(3, &[(0, 1, 1.0), (1, 2, 1.0), (0, 2, 5.0)]);
+        let path = find(&mut app, i[0], i[2], true).unwrap();
+        assert_eq!(path.identifiers, vec![i[0], i[1], i[2]]);
+        assert_eq!(path.connections, vec![c[0], c[1]]);
+        assert_eq!(path.cost, Some(2.0));
+    }
+
+    #[test]
+    fn direction() {
+        let (mut app, i, c) = graph(3, &[(1, 0, 1.0)]);
+        assert!(find(&mut app, i[0], i[1], true).is_none());
+        let path = find(&mut app, i[0], i[1], false).unwrap();
+        assert_eq!(path.connections, vec![c[0]]);
+        assert!(find(&mut app, i[0], i[2], false).is_none());
+    }
+
+    #[test]
+    fn dijkstra_takes_more_hops_when_they_are_shorter() {
+        let (a, b, c) = (
+            Entity::from_raw(0),
+            Entity::from_raw(1),
+            Entity::from_raw(2),
+        );
+        // 0 -> 1 weighs 4, 0 -> 2 -> 1 weighs 2.
+        let adjacency = vec![vec![(1, 4.0, a), (2, 1.0, b)], vec![], vec![(1, 1.0, c)]];
+        let predecessors = dijkstra(&adjacency, 0);
+        assert_eq!(predecessors[0], None);
+        assert_eq!(predecessors[1], Some((2, 1.0, c)));
+        assert_eq!(predecessors[2], Some((0, 1.0, b)));
+    }
+}
@@ -1,11 +1,17 @@
 use bevy::prelude::*;
 use bevy_inspector_egui::prelude::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
 
-#[derive(Reflect, Resource, InspectorOptions)]
+#[derive(Reflect, Resource, InspectorOptions, Serialize, Deserialize)]
 #[reflect(Resource, InspectorOptions)]
+#[serde(default)]
 pub struct Configuration {
     pub container_size: f32,
     pub animation_duration: u64,
+    /// Seed of `SeededRng`, shown so a run can be repeated.
+    pub seed: u64,
 }
 
 impl Default for Configuration {
@@ -13,6 +19,28 @@ impl Default for Configuration {
         Self {
             container_size: 4.0,
             animation_duration: 2,
+            seed: 0,
         }
     }
 }
+
+/// The one source of randomness. Generating, placing and selecting identifiers
+/// all draw from it, so the same seed and the same sequence of actions give the
+/// same graph and positions.
+#[derive(Resource)]
+pub struct SeededRng(StdRng);
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        Self(StdRng::seed_from_u64(seed))
+    }
+
+    /// Restarts the sequence from `seed`.
+    pub fn reseed(&mut self, seed: u64) {
+        self.0 = StdRng::seed_from_u64(seed);
+    }
+
+    pub fn rng(&mut self) -> &mut StdRng {
+        &mut self.0
+    }
+}
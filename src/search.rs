@@ -0,0 +1,151 @@
+use bevy::prelude::*;
+use bevy_egui::EguiContext;
+use bevy_inspector_egui::egui;
+use bevy_window::PrimaryWindow;
+
+use crate::events::SelectIdentifierEvent;
+use crate::graph_index::GraphIndex;
+use crate::identifiers::{Attributes, Identifier, SelectedIdentifier};
+
+/// Results listed for a search, best match first.
+const MAX_RESULTS: usize = 50;
+
+/// Search box text in the top bar and the identifiers matching it.
+#[derive(Resource, Default)]
+pub struct Search {
+    pub query: String,
+    pub results: Vec<Entity>,
+}
+
+pub struct SearchPlugin;
+
+impl Plugin for SearchPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Search>()
+            .add_systems(Update, update_search_results);
+    }
+}
+
+pub fn search_ui(
+    query: Query<&mut EguiContext, With<PrimaryWindow>>,
+    search: Res<Search>,
+    selected_identifier: Res<SelectedIdentifier>,
+    identifier_query: Query<&Identifier>,
+    mut ev: EventWriter<SelectIdentifierEvent>,
+) {
+    let mut egui_context = query.single().clone();
+
+    egui::Window::new("Search")
+        .vscroll(false)
+        .hscroll(false)
+        .default_width(250.0)
+        .resizable(false)
+        .show(egui_context.get_mut(), |ui| {
+            if search.results.is_empty() {
+                ui.label("No matches");
+                return;
+            }
+            egui::ScrollArea::vertical()
+                .max_height(300.0)
+                .show(ui, |ui| {
+                    for &entity in search.results.iter() {
+                        let Ok(identifier) = identifier_query.get(entity) else {
+                            continue;
+                        };
+                        let text = match &identifier.label {
+                            Some(label) => format!("{} ({})", label, identifier.key),
+                            None => identifier.key.clone(),
+                        };
+                        if ui
+                            .selectable_label(selected_identifier.0 == Some(entity), text)
+                            .clicked()
+                        {
+                            ev.send(SelectIdentifierEvent(entity));
+                        }
+                    }
+                });
+        });
+}
+
+fn update_search_results(
+    mut search: ResMut<Search>,
+    graph_index: Res<GraphIndex>,
+    identifier_query: Query<(&Identifier, Option<&Attributes>)>,
+) {
+    if !search.is_changed() && !graph_index.is_changed() {
+        return;
+    }
+    let query = search.query.trim().to_lowercase();
+    if query.is_empty() {
+        search.results.clear();
+        return;
+    }
+
+    let mut matches: Vec<(i32, &str, Entity)> = (0..graph_index.len())
+        .filter_map(|i| {
+            let entity = graph_index.entity(i);
+            let (identifier, attributes) = identifier_query.get(entity).ok()?;
+            let score = std::iter::once(identifier.key.as_str())
+                .chain(identifier.label.as_deref())
+                .chain(
+                    attributes
+                        .into_iter()
+                        .flat_map(|a| a.0.values().map(String::as_str)),
+                )
+                .filter_map(|text| fuzzy_score(&query, text))
+                .max()?;
+            Some((score, identifier.key.as_str(), entity))
+        })
+        .collect();
+    matches.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+    let results = matches
+        .into_iter()
+        .take(MAX_RESULTS)
+        .map(|(_, _, entity)| entity)
+        .collect();
+    search.results = results;
+}
+
+/// How well the lowercase `query` matches `text`, higher is better. Substrings
+/// rank above scattered matches, earlier and tighter matches above later ones.
+/// `None` when the characters of `query` do not all appear in order.
+fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let text = text.to_lowercase();
+    if let Some(position) = text.find(query) {
+        return Some(1000 - position.min(999) as i32);
+    }
+
+    let mut score = 0;
+    let mut chars = text.chars().enumerate();
+    let mut previous: Option<usize> = None;
+    for wanted in query.chars() {
+        let (i, _) = chars.find(|(_, c)| *c == wanted)?;
+        let gap = i - previous.map_or(0, |p| p + 1);
+        // Consecutive characters count more, gaps cost up to a few points.
+        score += (if gap == 0 { 5 } else { 1 }) - gap.min(3) as i32;
+        previous = Some(i);
+    }
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn substrings_rank_by_position() {
+        assert_eq!(fuzzy_score("abc", "abc"), Some(1000));
+        assert_eq!(fuzzy_score("abc", "xxABCxx"), Some(998));
+        assert_eq!(fuzzy_score("", "anything"), Some(1000));
+    }
+
+    #[test]
+    fn scattered_matches() {
+        // a: 5, c after a gap of one: 0, e after a gap of one: 0
+        assert_eq!(fuzzy_score("ace", "abcde"), Some(5));
+        assert!(fuzzy_score("ab", "a_b") > fuzzy_score("ab", "a___b"));
+        assert!(fuzzy_score("ab", "a_b") < fuzzy_score("ab", "xxxab"));
+        assert_eq!(fuzzy_score("acb", "abc"), None);
+        assert_eq!(fuzzy_score("z", "abc"), None);
+    }
+}
@@ -0,0 +1,318 @@
+use bevy::prelude::*;
+use bevy_egui::EguiContext;
+use bevy_inspector_egui::egui;
+use bevy_panorbit_camera::PanOrbitCamera;
+use bevy_window::PrimaryWindow;
+
+use crate::connectivity::ConnectedComponents;
+use crate::events::{
+    DeleteIdentifiersEvent, DeselectIdentifierEvent, ExpandNeighbourhoodEvent,
+    SelectIdentifiersEvent, SelectionEvent, UnpinAllEvent,
+};
+use crate::identifiers::{
+    update_identifiers_and_connections, Connection, Hidden, Identifier, Neighbourhood, Pinned,
+    SelectedIdentifier, Selection, MAX_NEIGHBOURHOOD_DEPTH,
+};
+
+/// Distance in logical pixels the pointer has to move before a shift-press
+/// becomes a box or lasso instead of a click.
+const MIN_DRAG_DISTANCE: f32 = 5.0;
+
+pub struct SelectionPlugin;
+
+impl Plugin for SelectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, drag_select).add_systems(
+            Update,
+            // Despawned identifiers must not be highlighted in the same frame.
+            selection_actions.after(update_identifiers_and_connections),
+        );
+    }
+}
+
+pub fn selection_ui(
+    query: Query<&mut EguiContext, With<PrimaryWindow>>,
+    selected_identifier: Res<SelectedIdentifier>,
+    selection: Res<Selection>,
+    mut neighbourhood: ResMut<Neighbourhood>,
+    identifier_query: Query<&Identifier>,
+    mut ev: EventWriter<SelectionEvent>,
+    mut ev_deselect: EventWriter<DeselectIdentifierEvent>,
+    mut ev_expand: EventWriter<ExpandNeighbourhoodEvent>,
+    mut ev_unpin_all: EventWriter<UnpinAllEvent>,
+) {
+    let mut egui_context = query.single().clone();
+
+    egui::Window::new("Selection")
+        .vscroll(false)
+        .hscroll(false)
+        .default_width(250.0)
+        .resizable(false)
+        .show(egui_context.get_mut(), |ui| {
+            ui.label("Shift-click or shift-drag to add identifiers, ctrl-shift-drag for a lasso.");
+            ui.horizontal(|ui| {
+                ui.label(format!("{} selected", selection.0.len()));
+                if let Some(identifier) = selected_identifier
+                    .0
+                    .and_then(|entity| identifier_query.get(entity).ok())
+                {
+                    ui.label(format!("(last {})", identifier.key));
+                }
+            });
+            ui.horizontal(|ui| {
+                let mut depth = neighbourhood.depth;
+                if ui
+                    .add(egui::Slider::new(&mut depth, 1..=MAX_NEIGHBOURHOOD_DEPTH).text("Hops"))
+                    .changed()
+                {
+                    neighbourhood.depth = depth;
+                }
+                if ui.button("Expand").clicked() {
+                    ev_expand.send(ExpandNeighbourhoodEvent);
+                }
+            });
+            ui.add_enabled_ui(!selection.0.is_empty(), |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("Hide").clicked() {
+                        ev.send(SelectionEvent::Hide);
+                    }
+                    if ui.button("Delete").clicked() {
+                        ev.send(SelectionEvent::Delete);
+                    }
+                    if ui.button("Pin").clicked() {
+                        ev.send(SelectionEvent::Pin);
+                    }
+                    if ui.button("Unpin").clicked() {
+                        ev.send(SelectionEvent::Unpin);
+                    }
+                });
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Show all").clicked() {
+                    ev.send(SelectionEvent::ShowAll);
+                }
+                if ui.button("Unpin all").clicked() {
+                    ev_unpin_all.send(UnpinAllEvent);
+                }
+                if ui.button("Clear selection").clicked() {
+                    ev_deselect.send(DeselectIdentifierEvent);
+                }
+            });
+        });
+}
+
+/// Shift-drag selects the identifiers inside a rectangle, ctrl-shift-drag
+/// inside a free-hand lasso. The camera stays put while dragging.
+fn drag_select(
+    mouse: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    // Pointer positions since the drag started, empty when not dragging.
+    mut drag: Local<Vec<Vec2>>,
+    window_query: Query<(&Window, &EguiContext), With<PrimaryWindow>>,
+    mut camera_query: Query<(&Camera, &GlobalTransform, &mut PanOrbitCamera)>,
+    identifier_query: Query<(Entity, &GlobalTransform), (With<Identifier>, Without<Hidden>)>,
+    mut ev: EventWriter<SelectIdentifiersEvent>,
+) {
+    let Ok((window, egui_context)) = window_query.get_single() else {
+        return;
+    };
+    let Ok((camera, camera_transform, mut pan_orbit)) = camera_query.get_single_mut() else {
+        return;
+    };
+    let mut egui_context = egui_context.clone();
+    let ctx = egui_context.get_mut();
+
+    if mouse.just_pressed(MouseButton::Left)
+        && keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight])
+        && !ctx.is_pointer_over_area()
+    {
+        if let Some(cursor) = window.cursor_position() {
+            drag.clear();
+            drag.push(cursor);
+            pan_orbit.enabled = false;
+        }
+    }
+    if drag.is_empty() {
+        return;
+    }
+
+    if let Some(cursor) = window.cursor_position() {
+        if drag[drag.len() - 1].distance(cursor) >= 2.0 {
+            drag.push(cursor);
+        }
+    }
+    let lasso = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let start = drag[0];
+    let end = drag[drag.len() - 1];
+
+    if mouse.pressed(MouseButton::Left) {
+        // Logical pixels to egui points
+        let scale = window.scale_factor() as f32 / ctx.pixels_per_point();
+        let point = |position: Vec2| egui::pos2(position.x * scale, position.y * scale);
+        let painter = ctx.layer_painter(egui::LayerId::new(
+            egui::Order::Foreground,
+            egui::Id::new("selection"),
+        ));
+        let stroke = egui::Stroke::new(1.0f32, egui::Color32::WHITE);
+        if lasso {
+            painter.add(egui::Shape::closed_line(
+                drag.iter().copied().map(point).collect(),
+                stroke,
+            ));
+        } else {
+            painter.rect_stroke(
+                egui::Rect::from_two_pos(point(start), point(end)),
+                0.0,
+                stroke,
+            );
+        }
+        return;
+    }
+
+    pan_orbit.enabled = true;
+    let outline = std::mem::take(&mut *drag);
+    // Short drags are clicks, handled by the identifier itself.
+    if outline
+        .iter()
+        .all(|position| position.distance(start) < MIN_DRAG_DISTANCE)
+    {
+        return;
+    }
+    let rect = Rect::from_corners(start, end);
+    let entities = identifier_query
+        .iter()
+        .filter(|(_, transform)| {
+            camera
+                .world_to_viewport(camera_transform, transform.translation())
+                .is_some_and(|position| {
+                    if lasso {
+                        polygon_contains(&outline, position)
+                    } else {
+                        rect.contains(position)
+                    }
+                })
+        })
+        .map(|(entity, _)| entity)
+        .collect();
+    ev.send(SelectIdentifiersEvent {
+        entities,
+        add: true,
+    });
+}
+
+/// Even-odd rule: a point is inside when a ray from it crosses the outline an
+/// odd number of times.
+fn polygon_contains(polygon: &[Vec2], point: Vec2) -> bool {
+    let mut inside = false;
+    for (i, a) in polygon.iter().enumerate() {
+        let b = polygon[(i + 1) % polygon.len()];
+        if (a.y > point.y) != (b.y > point.y)
+            && point.x < a.x + (point.y - a.y) * (b.x - a.x) / (b.y - a.y)
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+fn selection_actions(
+    mut commands: Commands,
+    mut ev: EventReader<SelectionEvent>,
+    mut ev_delete: EventWriter<DeleteIdentifiersEvent>,
+    selection: Res<Selection>,
+    mut neighbourhood: ResMut<Neighbourhood>,
+    mut components: ResMut<ConnectedComponents>,
+    identifier_query: Query<Entity, With<Identifier>>,
+    hidden_query: Query<Entity, With<Hidden>>,
+    connection_query: Query<(Entity, &Connection)>,
+) {
+    // The selection may still hold identifiers despawned since it was made.
+    let selected = || {
+        selection
+            .0
+            .iter()
+            .copied()
+            .filter(|&entity| identifier_query.contains(entity))
+    };
+    let incident = |connection: &Connection| {
+        selection.0.contains(&connection.from) || selection.0.contains(&connection.to)
+    };
+    for e in ev.read() {
+        match e {
+            SelectionEvent::Hide => {
+                for entity in selected() {
+                    commands.entity(entity).insert((Hidden, Visibility::Hidden));
+                }
+                for (entity, connection) in connection_query.iter() {
+                    if incident(connection) {
+                        commands.entity(entity).insert(Visibility::Hidden);
+                    }
+                }
+            }
+            SelectionEvent::ShowAll => {
+                for entity in hidden_query.iter() {
+                    commands
+                        .entity(entity)
+                        .remove::<Hidden>()
+                        .insert(Visibility::Visible);
+                }
+                for hidden in components.hidden.iter_mut() {
+                    *hidden = false;
+                }
+                // Highlighted again once they are no longer hidden, which shows
+                // the connections of the whole neighbourhood.
+                neighbourhood.set_changed();
+            }
+            SelectionEvent::Delete => {
+                let entities: Vec<Entity> = selected().collect();
+                info!("Deleted {} identifiers", entities.len());
+                ev_delete.send(DeleteIdentifiersEvent(entities));
+            }
+            SelectionEvent::Pin => {
+                for entity in selected() {
+                    commands.entity(entity).insert(Pinned);
+                }
+            }
+            SelectionEvent::Unpin => {
+                for entity in selected() {
+                    commands.entity(entity).remove::<Pinned>();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convex_polygon() {
+        let square = [
+            Vec2::new(0.0, 0.0),
+            Vec2::new(2.0, 0.0),
+            Vec2::new(2.0, 2.0),
+            Vec2::new(0.0, 2.0),
+        ];
+        assert!(polygon_contains(&square, Vec2::new(1.0, 1.0)));
+        assert!(!polygon_contains(&square, Vec2::new(3.0, 1.0)));
+        assert!(!polygon_contains(&square, Vec2::new(1.0, -1.0)));
+    }
+
+    #[test]
+    fn concave_polygon() {
+        // L shape, with the notch at the top right
+        let l = [
+            Vec2::new(0.0, 0.0),
+            Vec2::new(3.0, 0.0),
+            Vec2::new(3.0, 1.0),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(1.0, 3.0),
+            Vec2::new(0.0, 3.0),
+        ];
+        assert!(polygon_contains(&l, Vec2::new(0.5, 2.0)));
+        assert!(polygon_contains(&l, Vec2::new(2.0, 0.5)));
+        assert!(!polygon_contains(&l, Vec2::new(2.0, 2.0)));
+        assert!(!polygon_contains(&[], Vec2::ZERO));
+    }
+}
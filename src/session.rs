@@ -0,0 +1,471 @@
+use bevy::prelude::*;
+use bevy_egui::EguiContext;
+use bevy_inspector_egui::egui;
+use bevy_panorbit_camera::PanOrbitCamera;
+use bevy_window::PrimaryWindow;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use crate::assets::{ColorMaterials, MyAssets};
+use crate::cli::CliArgs;
+use crate::communities::Communities;
+use crate::connectivity::ConnectedComponents;
+use crate::events::{OpenSessionEvent, SaveSessionEvent};
+use crate::graph_index::GraphIndex;
+use crate::history::History;
+use crate::identifiers::{
+    spawn_connection, spawn_identifier, Attributes, Connection, ConnectionKind, Hidden, Identifier,
+    IdentifierMaterial, Neighbourhood, Pinned, SelectedIdentifier, Selection, TargetIdentifier,
+};
+use crate::layout::LayoutSettings;
+use crate::paths::PathSettings;
+use crate::resources::{Configuration, SeededRng};
+use crate::simulation::ForceAtlas2Settings;
+
+/// Bumped when a session written by this version can no longer be read by older ones.
+const SESSION_VERSION: u32 = 1;
+
+/// Everything needed to come back to the same view: the graph with its
+/// positions, the camera, the selection and the settings of the algorithms.
+/// Identifiers are referred to by their index in `identifiers`.
+#[derive(Serialize, Deserialize)]
+struct Session {
+    version: u32,
+    configuration: Configuration,
+    camera: CameraState,
+    identifiers: Vec<IdentifierState>,
+    connections: Vec<ConnectionState>,
+    #[serde(default)]
+    selection: Vec<usize>,
+    /// The identifier the camera follows, part of `selection`.
+    #[serde(default)]
+    selected: Option<usize>,
+    /// Other end of the shortest path.
+    #[serde(default)]
+    target: Option<usize>,
+    #[serde(default = "default_depth")]
+    neighbourhood_depth: usize,
+    #[serde(default)]
+    forceatlas2: ForceAtlas2Settings,
+    #[serde(default)]
+    layout: LayoutSettings,
+    #[serde(default)]
+    paths: PathSettings,
+}
+
+fn default_depth() -> usize {
+    Neighbourhood::default().depth
+}
+
+#[derive(Serialize, Deserialize)]
+struct CameraState {
+    focus: [f32; 3],
+    alpha: f32,
+    beta: f32,
+    radius: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct IdentifierState {
+    key: String,
+    #[serde(default)]
+    label: Option<String>,
+    position: [f32; 3],
+    scale: f32,
+    /// RGBA of its own material, `None` for the default one.
+    #[serde(default)]
+    color: Option<[f32; 4]>,
+    #[serde(default)]
+    attributes: BTreeMap<String, String>,
+    #[serde(default)]
+    pinned: bool,
+    #[serde(default)]
+    hidden: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ConnectionState {
+    from: usize,
+    to: usize,
+    weight: f32,
+    #[serde(default)]
+    kind: ConnectionKind,
+    #[serde(default)]
+    attributes: BTreeMap<String, String>,
+}
+
+/// Outcome of the last save or open, shown in the Session window.
+#[derive(Resource, Default)]
+pub struct SessionStatus {
+    pub message: Option<String>,
+}
+
+pub struct SessionPlugin;
+
+impl Plugin for SessionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SessionStatus>()
+            .add_systems(Startup, open_session_from_command_line)
+            .add_systems(Update, save_session)
+            // After `Update`, so nothing highlights the despawned graph, and a
+            // frame after the restored selection is zoomed to the camera is set.
+            .add_systems(PostUpdate, open_session);
+    }
+}
+
+pub struct SessionForm {
+    path: String,
+}
+impl Default for SessionForm {
+    fn default() -> Self {
+        Self {
+            path: "session.json".to_string(),
+        }
+    }
+}
+
+pub fn session_ui(
+    query: Query<&mut EguiContext, With<PrimaryWindow>>,
+    mut form: Local<SessionForm>,
+    status: Res<SessionStatus>,
+    mut ev_save: EventWriter<SaveSessionEvent>,
+    mut ev_open: EventWriter<OpenSessionEvent>,
+) {
+    let mut egui_context = query.single().clone();
+
+    egui::Window::new("Session")
+        .vscroll(false)
+        .hscroll(false)
+        .default_width(250.0)
+        .resizable(false)
+        .show(egui_context.get_mut(), |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Path");
+                ui.text_edit_singleline(&mut form.path);
+            });
+            ui.add_enabled_ui(!form.path.trim().is_empty(), |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("Save").clicked() {
+                        ev_save.send(SaveSessionEvent {
+                            path: form.path.trim().into(),
+                        });
+                    }
+                    if ui
+                        .button("Open")
+                        .on_hover_text("Replaces the current graph")
+                        .clicked()
+                    {
+                        ev_open.send(OpenSessionEvent {
+                            path: form.path.trim().into(),
+                        });
+                    }
+                });
+            });
+
+            if let Some(message) = &status.message {
+                ui.separator();
+                ui.label(message);
+            }
+        });
+}
+
+fn open_session_from_command_line(args: Res<CliArgs>, mut ev: EventWriter<OpenSessionEvent>) {
+    if let Some(path) = &args.session {
+        ev.send(OpenSessionEvent { path: path.clone() });
+    }
+}
+
+fn save_session(
+    mut ev: EventReader<SaveSessionEvent>,
+    mut status: ResMut<SessionStatus>,
+    configuration: Res<Configuration>,
+    forceatlas2: Res<ForceAtlas2Settings>,
+    layout: Res<LayoutSettings>,
+    paths: Res<PathSettings>,
+    neighbourhood: Res<Neighbourhood>,
+    selection: Res<Selection>,
+    selected_identifier: Res<SelectedIdentifier>,
+    target_identifier: Res<TargetIdentifier>,
+    materials: Res<Assets<StandardMaterial>>,
+    graph_index: Res<GraphIndex>,
+    camera_query: Query<&PanOrbitCamera>,
+    identifier_query: Query<(
+        &Identifier,
+        &Transform,
+        Option<&Attributes>,
+        Option<&IdentifierMaterial>,
+        Has<Pinned>,
+        Has<Hidden>,
+    )>,
+    connection_query: Query<(&Connection, Option<&Attributes>)>,
+) {
+    for e in ev.read() {
+        let Ok(camera) = camera_query.get_single() else {
+            continue;
+        };
+        // Entity -> index in `identifiers`
+        let mut indices = HashMap::new();
+        let mut identifiers = vec![];
+        for i in 0..graph_index.len() {
+            let entity = graph_index.entity(i);
+            let Ok((identifier, transform, attributes, material, pinned, hidden)) =
+                identifier_query.get(entity)
+            else {
+                continue;
+            };
+            indices.insert(entity, identifiers.len());
+            identifiers.push(IdentifierState {
+                key: identifier.key.clone(),
+                label: identifier.label.clone(),
+                position: transform.translation.to_array(),
+                scale: transform.scale.x,
+                color: material
+                    .and_then(|material| materials.get(&material.0))
+                    .map(|material| material.base_color.as_rgba_f32()),
+                attributes: attributes.map(|a| a.0.clone()).unwrap_or_default(),
+                pinned,
+                hidden,
+            });
+        }
+        let connections = connection_query
+            .iter()
+            .filter_map(|(connection, attributes)| {
+                Some(ConnectionState {
+                    from: *indices.get(&connection.from)?,
+                    to: *indices.get(&connection.to)?,
+                    weight: connection.weight,
+                    kind: connection.kind,
+                    attributes: attributes.map(|a| a.0.clone()).unwrap_or_default(),
+                })
+            })
+            .collect();
+        let index = |entity: Option<Entity>| entity.and_then(|e| indices.get(&e).copied());
+
+        let session = Session {
+            version: SESSION_VERSION,
+            configuration: Configuration {
+                container_size: configuration.container_size,
+                animation_duration: configuration.animation_duration,
+                seed: configuration.seed,
+            },
+            camera: CameraState {
+                focus: camera.target_focus.to_array(),
+                alpha: camera.target_alpha,
+                beta: camera.target_beta,
+                radius: camera.target_radius,
+            },
+            identifiers,
+            connections,
+            selection: selection
+                .0
+                .iter()
+                .filter_map(|&entity| index(Some(entity)))
+                .collect(),
+            selected: index(selected_identifier.0),
+            target: index(target_identifier.0),
+            neighbourhood_depth: neighbourhood.depth,
+            forceatlas2: forceatlas2.clone(),
+            layout: layout.clone(),
+            paths: PathSettings {
+                directed: paths.directed,
+            },
+        };
+
+        match write_session(&e.path, &session) {
+            Ok(()) => {
+                info!(
+                    "Saved {} identifiers and {} connections to {}",
+                    session.identifiers.len(),
+                    session.connections.len(),
+                    e.path.display()
+                );
+                status.message = Some(format!("Saved to {}", e.path.display()));
+            }
+            Err(err) => {
+                error!("Failed to save {}: {}", e.path.display(), err);
+                status.message = Some(format!("Failed to save {}: {}", e.path.display(), err));
+            }
+        }
+    }
+}
+
+fn open_session(
+    mut commands: Commands,
+    mut ev: EventReader<OpenSessionEvent>,
+    mut status: ResMut<SessionStatus>,
+    // Camera of the session opened in the previous frame, set after the
+    // restored selection has been zoomed to.
+    mut camera_state: Local<Option<CameraState>>,
+    mut history: ResMut<History>,
+    mut configuration: ResMut<Configuration>,
+    mut seeded_rng: ResMut<SeededRng>,
+    mut forceatlas2: ResMut<ForceAtlas2Settings>,
+    mut layout: ResMut<LayoutSettings>,
+    mut paths: ResMut<PathSettings>,
+    (mut neighbourhood, mut components, mut communities): (
+        ResMut<Neighbourhood>,
+        ResMut<ConnectedComponents>,
+        ResMut<Communities>,
+    ),
+    (mut selection, mut selected_identifier, mut target_identifier): (
+        ResMut<Selection>,
+        ResMut<SelectedIdentifier>,
+        ResMut<TargetIdentifier>,
+    ),
+    my_assets: Res<MyAssets>,
+    (mut color_materials, mut materials): (
+        ResMut<ColorMaterials>,
+        ResMut<Assets<StandardMaterial>>,
+    ),
+    mut camera_query: Query<&mut PanOrbitCamera>,
+    existing_query: Query<Entity, Or<(With<Identifier>, With<Connection>)>>,
+) {
+    if let Some(state) = camera_state.take() {
+        if let Ok(mut camera) = camera_query.get_single_mut() {
+            let focus = Vec3::from_array(state.focus);
+            camera.focus = focus;
+            camera.target_focus = focus;
+            camera.alpha = Some(state.alpha);
+            camera.target_alpha = state.alpha;
+            camera.beta = Some(state.beta);
+            camera.target_beta = state.beta;
+            camera.radius = Some(state.radius);
+            camera.target_radius = state.radius;
+            camera.force_update = true;
+        }
+    }
+
+    // Only the last one counts, each replaces the graph.
+    let Some(e) = ev.read().last() else {
+        return;
+    };
+    let session = match read_session(&e.path) {
+        Ok(session) => session,
+        Err(err) => {
+            error!("Failed to open {}: {}", e.path.display(), err);
+            status.message = Some(format!("Failed to open {}: {}", e.path.display(), err));
+            return;
+        }
+    };
+
+    for entity in existing_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    if configuration.seed != session.configuration.seed {
+        seeded_rng.reseed(session.configuration.seed);
+    }
+    *configuration = session.configuration;
+    *forceatlas2 = session.forceatlas2;
+    *layout = session.layout;
+    *paths = session.paths;
+    neighbourhood.depth = session.neighbourhood_depth;
+    // Both describe the old graph.
+    *components = ConnectedComponents::default();
+    *communities = Communities::default();
+
+    let positions: Vec<Vec3> = session
+        .identifiers
+        .iter()
+        .map(|state| Vec3::from_array(state.position))
+        .collect();
+    let entities: Vec<Entity> = session
+        .identifiers
+        .into_iter()
+        .map(|state| {
+            let entity = spawn_identifier(
+                &mut commands,
+                &my_assets,
+                &configuration,
+                Identifier {
+                    key: state.key,
+                    label: state.label,
+                },
+                Vec3::from_array(state.position),
+                state.scale,
+            );
+            if !state.attributes.is_empty() {
+                commands.entity(entity).insert(Attributes(state.attributes));
+            }
+            if let Some(color) = state.color {
+                let material = color_materials.get_or_add(Color::from(color), &mut materials);
+                commands
+                    .entity(entity)
+                    .insert((material.clone(), IdentifierMaterial(material)));
+            }
+            if state.pinned {
+                commands.entity(entity).insert(Pinned);
+            }
+            if state.hidden {
+                commands.entity(entity).insert((Hidden, Visibility::Hidden));
+            }
+            entity
+        })
+        .collect();
+
+    let mut spawned = entities.clone();
+    for state in session.connections.iter() {
+        let (Some(&from), Some(&to)) = (entities.get(state.from), entities.get(state.to)) else {
+            continue;
+        };
+        let entity = spawn_connection(
+            &mut commands,
+            &my_assets,
+            &configuration,
+            Connection {
+                from,
+                to,
+                weight: state.weight,
+                kind: state.kind,
+            },
+            positions[state.from],
+            positions[state.to],
+        );
+        if !state.attributes.is_empty() {
+            commands
+                .entity(entity)
+                .insert(Attributes(state.attributes.clone()));
+        }
+        spawned.push(entity);
+    }
+    // The opened graph is a new starting point, the edits to the old one are gone.
+    history.restart(spawned);
+
+    let entity = |index: Option<usize>| index.and_then(|i| entities.get(i).copied());
+    selection.0 = session
+        .selection
+        .iter()
+        .filter_map(|&i| entity(Some(i)))
+        .collect();
+    selected_identifier.0 = entity(session.selected);
+    selection.0.extend(selected_identifier.0);
+    target_identifier.0 = entity(session.target);
+    *camera_state = Some(session.camera);
+
+    info!(
+        "Opened {} identifiers and {} connections from {}",
+        entities.len(),
+        session.connections.len(),
+        e.path.display()
+    );
+    status.message = Some(format!("Opened {}", e.path.display()));
+}
+
+fn write_session(path: &Path, session: &Session) -> std::io::Result<()> {
+    let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    serde_json::to_writer_pretty(file, session)?;
+    Ok(())
+}
+
+fn read_session(path: &Path) -> Result<Session, String> {
+    let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let session: Session = serde_json::from_str(&text).map_err(|err| err.to_string())?;
+    if session.version > SESSION_VERSION {
+        return Err(format!(
+            "session version {} is newer than the supported {}",
+            session.version, SESSION_VERSION
+        ));
+    }
+    Ok(session)
+}
@@ -1,20 +1,25 @@
-use crate::assets::MyAssets;
+use crate::assets::{MyAssets, IDENTIFIER_RADIUS};
+use crate::connectivity::{isolate_positions, IsolatePolicy};
 use crate::events::*;
-use crate::identifiers::{Connection, Identifier};
-use crate::resources::Configuration;
+use crate::graph_index::GraphIndex;
+use crate::history::{Edit, History};
+use crate::identifiers::{
+    spawn_connection, spawn_identifier, Connection, ConnectionKind, Identifier, Pinned,
+    IDENTIFIER_SCALE,
+};
+use crate::jobs::{poll, Job, Jobs};
+use crate::layout::ease_identifiers_to;
+use crate::resources::{Configuration, SeededRng};
 use crate::util::random_point_in_sphere;
 use bevy::prelude::*;
 use bevy_easings::*;
 use bevy_egui::EguiContext;
 use bevy_inspector_egui::egui;
-use bevy_mod_picking::prelude::*;
-use bevy_mod_picking::PickableBundle;
 use bevy_window::PrimaryWindow;
 use forceatlas2::*;
-use graph::page_rank::PageRankConfig;
-use graph::prelude::*;
+use rand::rngs::StdRng;
 use rand::Rng;
-use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
 pub struct SimulationPlugin;
 
 impl Plugin for SimulationPlugin {
@@ -31,8 +36,15 @@ impl Plugin for SimulationPlugin {
             .register_type::<Connection>()
             .add_systems(Update, add_connections)
             .add_systems(Update, move_identifiers_randomly)
+            .init_resource::<ForceAtlas2Settings>()
+            .register_type::<ForceAtlas2Settings>()
+            .init_resource::<ForceAtlas2Presets>()
+            .init_resource::<LiveForceAtlas2>()
             .add_systems(Update, move_identifiers_forceatlas2)
-            .add_systems(Update, resize_identifiers_pagerank)
+            .add_systems(
+                Update,
+                (control_forceatlas2_live, run_forceatlas2_live).chain(),
+            )
             .add_systems(Update, update_connections_transforms)
             .add_systems(Update, add_identifiers);
     }
@@ -87,41 +99,131 @@ pub fn simulation_ui(
         });
 }
 
-pub struct Gravity(f32);
-impl Default for Gravity {
-    fn default() -> Self {
-        Self(0.3)
-    }
+/// Everything ForceAtlas2 is run with. Kept in a resource so the same settings,
+/// or a saved preset, reproduce a layout.
+#[derive(Reflect, Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[reflect(Resource)]
+#[serde(default)]
+pub struct ForceAtlas2Settings {
+    pub kg: f64,
+    pub ka: f64,
+    pub kr: f64,
+    pub lin_log: bool,
+    pub dissuade_hubs: bool,
+    pub strong_gravity: bool,
+    /// `kr_prime`, uses the identifier scale as its size.
+    pub prevent_overlapping: Option<f64>,
+    pub speed: f64,
+    /// Identifiers per thread in the repulsion step, `None` runs single threaded.
+    pub chunk_size: Option<usize>,
+    /// 2 lays the graph out flat on the XY plane, 3 in space.
+    pub dimensions: usize,
+    /// Barnes-Hut theta. Only used when built with the `barnes_hut` feature.
+    pub barnes_hut: Option<f64>,
+    /// Iterations of a one-shot run.
+    pub iterations: u32,
+    /// Iterations of a live run per frame.
+    pub iterations_per_frame: u32,
 }
 
-pub struct Atrraction(f32);
-impl Default for Atrraction {
+impl Default for ForceAtlas2Settings {
     fn default() -> Self {
-        Self(0.9)
+        Self {
+            kg: 0.3,
+            ka: 0.9,
+            kr: 0.05,
+            lin_log: false,
+            dissuade_hubs: true,
+            strong_gravity: true,
+            prevent_overlapping: None,
+            speed: 1.0,
+            chunk_size: Some(256),
+            dimensions: 3,
+            barnes_hut: None,
+            iterations: 100,
+            iterations_per_frame: 5,
+        }
     }
 }
 
-pub struct Repulsion(f32);
-impl Default for Repulsion {
-    fn default() -> Self {
-        Self(0.05)
+impl ForceAtlas2Settings {
+    pub fn to_settings(&self) -> Settings<f64> {
+        Settings {
+            #[cfg(feature = "barnes_hut")]
+            barnes_hut: self.barnes_hut,
+            chunk_size: self.chunk_size,
+            dimensions: self.dimensions,
+            dissuade_hubs: self.dissuade_hubs,
+            ka: self.ka,
+            kg: self.kg,
+            kr: self.kr,
+            lin_log: self.lin_log,
+            prevent_overlapping: self.prevent_overlapping,
+            speed: self.speed,
+            strong_gravity: self.strong_gravity,
+        }
     }
 }
 
-pub struct Iterations(u32);
-impl Default for Iterations {
+/// Named ForceAtlas2 settings to pick from in the ForceAtlas2 window.
+#[derive(Resource)]
+pub struct ForceAtlas2Presets(pub Vec<(String, ForceAtlas2Settings)>);
+
+impl Default for ForceAtlas2Presets {
     fn default() -> Self {
-        Self(100)
+        let default = ForceAtlas2Settings::default();
+        Self(vec![
+            ("Default".to_string(), default.clone()),
+            (
+                "Gephi".to_string(),
+                ForceAtlas2Settings {
+                    kg: 1.0,
+                    ka: 1.0,
+                    kr: 2.0,
+                    dissuade_hubs: false,
+                    strong_gravity: false,
+                    ..default.clone()
+                },
+            ),
+            (
+                "Communities (LinLog)".to_string(),
+                ForceAtlas2Settings {
+                    lin_log: true,
+                    dissuade_hubs: false,
+                    kr: 0.5,
+                    ..default.clone()
+                },
+            ),
+            (
+                "Flat".to_string(),
+                ForceAtlas2Settings {
+                    dimensions: 2,
+                    ..default.clone()
+                },
+            ),
+            (
+                "No overlap".to_string(),
+                ForceAtlas2Settings {
+                    prevent_overlapping: Some(100.0),
+                    ..default
+                },
+            ),
+        ])
     }
 }
 
+/// Name typed into the ForceAtlas2 window for saving a preset.
+#[derive(Default)]
+pub struct PresetName(String);
+
 pub fn force_atlas_ui(
     query: Query<&mut EguiContext, With<PrimaryWindow>>,
-    mut gravity: Local<Gravity>,
-    mut attraction: Local<Atrraction>,
-    mut repulsion: Local<Repulsion>,
-    mut iterations: Local<Iterations>,
+    mut settings: ResMut<ForceAtlas2Settings>,
+    mut presets: ResMut<ForceAtlas2Presets>,
+    mut preset_name: Local<PresetName>,
+    live: Res<LiveForceAtlas2>,
     mut ev: EventWriter<Forceatlas2Event>,
+    mut ev_live: EventWriter<Forceatlas2LiveEvent>,
 ) {
     let mut egui_context = query.single().clone();
 
@@ -131,60 +233,158 @@ pub fn force_atlas_ui(
         .default_width(250.0)
         .resizable(false)
         .show(egui_context.get_mut(), |ui| {
-            ui.add(egui::Slider::new(&mut gravity.0, 0.0..=2.0).text("Gravity"));
-            ui.add(egui::Slider::new(&mut attraction.0, 0.0..=2.0).text("Attraction"));
-            ui.add(egui::Slider::new(&mut repulsion.0, 0.0..=2.0).text("Repulsion"));
-            ui.add(egui::Slider::new(&mut iterations.0, 1..=1000).text("Iterations"));
+            let selected = presets
+                .0
+                .iter()
+                .find(|(_, preset)| *preset == *settings)
+                .map_or("Custom", |(name, _)| name.as_str())
+                .to_string();
+            egui::ComboBox::from_label("Preset")
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    for (name, preset) in presets.0.iter() {
+                        if ui.selectable_label(*preset == *settings, name).clicked() {
+                            *settings = preset.clone();
+                        }
+                    }
+                });
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut preset_name.0);
+                let name = preset_name.0.trim().to_string();
+                if ui
+                    .add_enabled(!name.is_empty(), egui::Button::new("Save preset"))
+                    .clicked()
+                {
+                    match presets.0.iter_mut().find(|(n, _)| *n == name) {
+                        Some((_, preset)) => *preset = settings.clone(),
+                        None => presets.0.push((name, settings.clone())),
+                    }
+                    preset_name.0.clear();
+                }
+            });
+            ui.separator();
+
+            ui.add(egui::Slider::new(&mut settings.kg, 0.0..=2.0).text("Gravity"));
+            ui.add(egui::Slider::new(&mut settings.ka, 0.0..=2.0).text("Attraction"));
+            ui.add(egui::Slider::new(&mut settings.kr, 0.0..=2.0).text("Repulsion"));
+            ui.add(egui::Slider::new(&mut settings.speed, 0.01..=10.0).text("Speed"));
+            ui.checkbox(&mut settings.lin_log, "LinLog mode");
+            ui.checkbox(&mut settings.dissuade_hubs, "Dissuade hubs");
+            ui.checkbox(&mut settings.strong_gravity, "Strong gravity");
+            optional_slider(
+                ui,
+                &mut settings.prevent_overlapping,
+                100.0,
+                1.0..=1000.0,
+                "Prevent overlapping",
+            );
+            let mut chunk_size = settings.chunk_size.map(|size| size as f64);
+            optional_slider(ui, &mut chunk_size, 256.0, 16.0..=4096.0, "Parallel chunks");
+            settings.chunk_size = chunk_size.map(|size| size as usize);
+            #[cfg(feature = "barnes_hut")]
+            optional_slider(
+                ui,
+                &mut settings.barnes_hut,
+                0.5,
+                0.1..=2.0,
+                "Barnes-Hut theta",
+            );
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut settings.dimensions, 2, "2D");
+                ui.radio_value(&mut settings.dimensions, 3, "3D");
+            });
+            ui.separator();
+
+            ui.add(egui::Slider::new(&mut settings.iterations, 1..=1000).text("Iterations"));
             if ui.button("Move identifiers forceatlas2").clicked() {
                 ev.send(Forceatlas2Event {
-                    settings: Settings {
-                        kg: gravity.0,
-                        ka: attraction.0,
-                        kr: repulsion.0,
-                        ..Default::default()
-                    },
-                    iterations: iterations.0,
+                    settings: settings.clone(),
                 });
             }
+            ui.separator();
+            ui.add(
+                egui::Slider::new(&mut settings.iterations_per_frame, 1..=50)
+                    .text("Iterations per frame"),
+            );
+            ui.horizontal(|ui| {
+                let start = if live.state == LiveLayoutState::Running {
+                    "Apply"
+                } else {
+                    "Start"
+                };
+                if ui.button(start).clicked() {
+                    ev_live.send(Forceatlas2LiveEvent::Start {
+                        settings: settings.clone(),
+                    });
+                }
+                if ui
+                    .add_enabled(
+                        live.state == LiveLayoutState::Running,
+                        egui::Button::new("Pause"),
+                    )
+                    .clicked()
+                {
+                    ev_live.send(Forceatlas2LiveEvent::Pause);
+                }
+                if ui
+                    .add_enabled(
+                        live.state != LiveLayoutState::Stopped,
+                        egui::Button::new("Stop"),
+                    )
+                    .clicked()
+                {
+                    ev_live.send(Forceatlas2LiveEvent::Stop);
+                }
+            });
+            ui.label(format!("{:?}, {} iterations", live.state, live.iterations));
+            ui.label(format!(
+                "Displacement {:.4} per iteration, {:.0} iterations/s",
+                live.displacement, live.iterations_per_second
+            ));
         });
 }
 
+/// Checkbox that turns `value` on with `default`, and a slider for it while it is on.
+fn optional_slider(
+    ui: &mut egui::Ui,
+    value: &mut Option<f64>,
+    default: f64,
+    range: std::ops::RangeInclusive<f64>,
+    text: &str,
+) {
+    ui.horizontal(|ui| {
+        let mut enabled = value.is_some();
+        if ui.checkbox(&mut enabled, text).changed() {
+            *value = enabled.then_some(default);
+        }
+        if let Some(value) = value {
+            ui.add(egui::Slider::new(value, range).logarithmic(true));
+        }
+    });
+}
+
 fn add_identifiers(
     mut commands: Commands,
     mut ev: EventReader<AddIdentifiersEvent>,
     configuration: Res<Configuration>,
+    mut seeded_rng: ResMut<SeededRng>,
     my_assets: ResMut<MyAssets>,
 ) {
     for e in ev.read() {
+        let rng = seeded_rng.rng();
         for _ in 0..e.count {
-            let (x, y, z) = random_point_in_sphere(configuration.container_size);
-            commands.spawn((
-                MaterialMeshBundle {
-                    mesh: my_assets.identifier_mesh_handle.clone(),
-                    material: my_assets.identifier_material_handle.clone(),
-                    ..Default::default()
+            let (x, y, z) = random_point_in_sphere(rng, configuration.container_size);
+            spawn_identifier(
+                &mut commands,
+                &my_assets,
+                &configuration,
+                Identifier {
+                    key: format!("did:example:{:016x}", rng.gen::<u64>()),
+                    label: None,
                 },
-                Transform::from_xyz(x, y, z)
-                    .with_scale(Vec3::new(0.0001, 0.0001, 0.0001))
-                    .ease_to(
-                        Transform::from_xyz(x, y, z).with_scale(Vec3::new(0.5, 0.5, 0.5)),
-                        bevy_easings::EaseFunction::QuadraticOut,
-                        bevy_easings::EasingType::Once {
-                            duration: std::time::Duration::from_secs(
-                                configuration.animation_duration,
-                            ),
-                        },
-                    ),
-                Identifier {},
-                PickableBundle::default(),
-                On::<Pointer<Click>>::run(
-                    |event: Listener<Pointer<Click>>,
-                     mut ev: EventWriter<SelectIdentifierEvent>| {
-                        info!("The pointer clicked entity {:?}", event.target);
-                        ev.send(SelectIdentifierEvent(event.target));
-                    },
-                ),
-            ));
+                Vec3::new(x, y, z),
+                IDENTIFIER_SCALE,
+            );
         }
     }
 }
@@ -193,65 +393,43 @@ fn add_connections(
     mut commands: Commands,
     mut ev: EventReader<AddConnectionsEvent>,
     configuration: Res<Configuration>,
-    identifier_query: Query<(Entity, &Transform), With<Identifier>>,
+    graph_index: Res<GraphIndex>,
+    mut seeded_rng: ResMut<SeededRng>,
+    identifier_query: Query<&Transform, With<Identifier>>,
     my_assets: ResMut<MyAssets>,
 ) {
     for e in ev.read() {
-        let mut rng = rand::thread_rng();
-        let identifier_count = identifier_query.iter().count() as u32;
+        let rng = seeded_rng.rng();
+        if graph_index.len() < 2 {
+            continue;
+        }
 
         for _ in 0..e.count {
-            let (rnd1, transform1) = identifier_query
-                .iter()
-                .nth(rng.gen_range(0..identifier_count as usize))
-                .unwrap();
-            let (rnd2, transform2) = identifier_query
-                .iter()
-                .nth(rng.gen_range(0..identifier_count as usize))
-                .unwrap();
+            let rnd1 = graph_index.entity(rng.gen_range(0..graph_index.len()));
+            let rnd2 = graph_index.entity(rng.gen_range(0..graph_index.len()));
 
             if rnd1 == rnd2 {
                 continue;
             }
+            let (Ok(transform1), Ok(transform2)) =
+                (identifier_query.get(rnd1), identifier_query.get(rnd2))
+            else {
+                continue;
+            };
 
-            let mid_point = transform1.translation.lerp(transform2.translation, 0.5);
-            let distance = transform1.translation.distance(transform2.translation);
-            let rotation = Quat::from_rotation_arc(
-                Vec3::Y,
-                (transform2.translation - transform1.translation).normalize(),
-            );
-
-            commands.spawn((
-                MaterialMeshBundle {
-                    mesh: my_assets.connection_mesh_handle.clone(),
-                    material: my_assets.connection_material_handle.clone(),
-                    // transform: Transform {
-                    //     translation: mid_point,
-                    //     rotation,
-                    //     scale: Vec3::new(1.0, distance, 1.0),
-                    // },
-                    visibility: Visibility::Visible,
-                    ..Default::default()
-                },
-                Transform::from_xyz(mid_point.x, mid_point.y, mid_point.z)
-                    .with_rotation(rotation)
-                    .with_scale(Vec3::new(1.0, 0.00001, 1.0))
-                    .ease_to(
-                        Transform::from_xyz(mid_point.x, mid_point.y, mid_point.z)
-                            .with_rotation(rotation)
-                            .with_scale(Vec3::new(1.0, distance, 1.0)),
-                        bevy_easings::EaseFunction::QuadraticInOut,
-                        bevy_easings::EasingType::Once {
-                            duration: std::time::Duration::from_secs(
-                                configuration.animation_duration,
-                            ),
-                        },
-                    ),
+            spawn_connection(
+                &mut commands,
+                &my_assets,
+                &configuration,
                 Connection {
                     from: rnd1,
                     to: rnd2,
+                    weight: 1.0,
+                    kind: ConnectionKind::Generic,
                 },
-            ));
+                transform1.translation,
+                transform2.translation,
+            );
         }
     }
 }
@@ -259,12 +437,26 @@ fn add_connections(
 fn move_identifiers_randomly(
     mut commands: Commands,
     mut ev: EventReader<MoveIdentifiersRndEvent>,
+    mut history: ResMut<History>,
     configuration: Res<Configuration>,
-    identifier_query: Query<(Entity, &Transform), With<Identifier>>,
+    mut seeded_rng: ResMut<SeededRng>,
+    graph_index: Res<GraphIndex>,
+    identifier_query: Query<(&Transform, Has<Pinned>), With<Identifier>>,
 ) {
     for _ in ev.read() {
-        for (entity, transform) in identifier_query.iter() {
-            let (x, y, z) = random_point_in_sphere(configuration.container_size);
+        let mut previous = vec![];
+        // Graph index order, so the same seed gives every identifier the same point.
+        for i in 0..graph_index.len() {
+            let entity = graph_index.entity(i);
+            let Ok((transform, pinned)) = identifier_query.get(entity) else {
+                continue;
+            };
+            // Drawn for pinned identifiers too, so pinning does not change where the others go.
+            let (x, y, z) = random_point_in_sphere(seeded_rng.rng(), configuration.container_size);
+            if pinned {
+                continue;
+            }
+            previous.push((entity, *transform));
             commands.entity(entity).insert(transform.ease_to(
                 Transform::from_xyz(x, y, z),
                 EaseFunction::QuarticOut,
@@ -273,6 +465,9 @@ fn move_identifiers_randomly(
                 },
             ));
         }
+        if !previous.is_empty() {
+            history.record(Edit::Transforms(previous));
+        }
     }
 }
 
@@ -302,264 +497,356 @@ fn update_connections_transforms(
     }
 }
 
-fn move_identifiers_forceatlas2(
-    mut commands: Commands,
-    mut ev: EventReader<Forceatlas2Event>,
-    configuration: Res<Configuration>,
-    identifier_query: Query<(Entity, &Transform), With<Identifier>>,
-    conn_query: Query<&Connection, With<Connection>>,
-) {
-    for settings in ev.read() {
-        // eprintln!("Generating graph...");
-        let edges: Vec<(usize, usize)> = conn_query
+/// ForceAtlas2 treats the graph as undirected and requires `n1 < n2` for every edge.
+fn undirected_edges(graph_index: &GraphIndex) -> Vec<(usize, usize)> {
+    graph_index
+        .edges()
+        .into_iter()
+        .filter(|(from, to)| from != to)
+        .map(|(from, to)| (from.min(to), from.max(to)))
+        .collect()
+}
+
+/// Builds a layout over every identifier in `graph_index`. `start` gives the
+/// position and scale of an identifier. With `rng` the layout starts from
+/// random points in the unit cube instead of the current positions.
+fn build_layout(
+    graph_index: &GraphIndex,
+    settings: &ForceAtlas2Settings,
+    start: impl Fn(Entity) -> Option<(Vec3, f32)>,
+    rng: Option<&mut StdRng>,
+) -> Option<Layout<f64>> {
+    let edges = undirected_edges(graph_index);
+    if edges.is_empty() {
+        return None;
+    }
+    let nodes: Vec<(Vec3, f32)> = (0..graph_index.len())
+        .map(|i| start(graph_index.entity(i)).unwrap_or((Vec3::ZERO, IDENTIFIER_SCALE)))
+        .collect();
+    let sizes = settings.prevent_overlapping.map(|_| {
+        nodes
             .iter()
-            .map(|connection| {
-                (
-                    connection.from.index() as usize,
-                    connection.to.index() as usize,
-                )
+            .map(|(_, scale)| (*scale * IDENTIFIER_RADIUS) as f64)
+            .collect()
+    });
+    let positions = match rng {
+        Some(rng) => (0..nodes.len() * settings.dimensions)
+            .map(|_| rng.gen_range(-1.0..1.0))
+            .collect(),
+        None => nodes
+            .iter()
+            .flat_map(|(position, _)| {
+                position.to_array()[..settings.dimensions]
+                    .iter()
+                    .map(|&x| x as f64)
+                    .collect::<Vec<_>>()
             })
-            .collect();
-
-        // count the number of unique nodes
-        let flattened: Vec<usize> = edges
-            .clone()
-            .into_iter()
-            .flat_map(|(a, b)| vec![a, b])
-            .collect();
-        let unique_values: HashSet<_> = flattened.into_iter().collect();
-
-        // get largest value
-        let max = unique_values.iter().max().unwrap();
-        let mut layout = Layout::<f32>::from_graph(
-            edges,
-            Nodes::Degree(*max + 1),
-            None,
-            None,
-            Settings {
-                #[cfg(feature = "barnes_hut")]
-                barnes_hut: None,
-                chunk_size: Some(256),
-                dimensions: 3,
-                dissuade_hubs: true,
-                ka: settings.settings.ka,
-                kg: settings.settings.kg,
-                kr: settings.settings.kr,
-                lin_log: false,
-                prevent_overlapping: None,
-                speed: 1.0,
-                strong_gravity: true,
-            },
-        );
-
-        // eprintln!("Computing layout...");
-        for _ in 0..settings.iterations {
-            // println!("{}/{}", i, ITERATIONS);
-            layout.iteration();
-        }
+            .collect(),
+    };
+    Some(Layout::from_position_graph(
+        edges,
+        Nodes::Degree(graph_index.len()),
+        sizes,
+        positions,
+        None,
+        settings.to_settings(),
+    ))
+}
 
-        for (h1, h2) in layout.edges.iter() {
-            if let Ok((entity, transform)) = identifier_query.get(Entity::from_raw(*h1 as u32)) {
-                let pos = layout.points.get(*h1);
-                commands.entity(entity).insert(transform.ease_to(
-                    Transform::from_xyz(pos[0], pos[1], pos[2]).with_scale(transform.scale),
-                    EaseFunction::QuarticOut,
-                    bevy_easings::EasingType::Once {
-                        duration: (std::time::Duration::from_secs(
-                            configuration.animation_duration,
-                        )),
-                    },
-                ));
-            }
+/// Graph indices and positions of the pinned identifiers among `entities`.
+fn pinned_positions(
+    entities: &[Entity],
+    pinned: impl Fn(Entity) -> Option<Vec3>,
+) -> Vec<(usize, Vec3)> {
+    entities
+        .iter()
+        .enumerate()
+        .filter_map(|(index, &entity)| Some((index, pinned(entity)?)))
+        .collect()
+}
 
-            if let Ok((entity, transform)) = identifier_query.get(Entity::from_raw(*h2 as u32)) {
-                let pos = layout.points.get(*h2);
-                commands.entity(entity).insert(transform.ease_to(
-                    Transform::from_xyz(pos[0], pos[1], pos[2]).with_scale(transform.scale),
-                    EaseFunction::QuarticOut,
-                    bevy_easings::EasingType::Once {
-                        duration: (std::time::Duration::from_secs(
-                            configuration.animation_duration,
-                        )),
-                    },
-                ));
-            }
+/// Puts pinned nodes back where they are held and stops them, as ForceAtlas2
+/// has no fixed nodes of its own.
+fn hold_pinned(layout: &mut Layout<f64>, pinned: &[(usize, Vec3)]) {
+    for &(index, position) in pinned {
+        for (coordinate, value) in layout
+            .points
+            .get_mut(index)
+            .iter_mut()
+            .zip(position.to_array())
+        {
+            *coordinate = value as f64;
         }
+        layout.speeds.get_mut(index).fill(0.0);
+        layout.old_speeds.get_mut(index).fill(0.0);
     }
 }
 
-fn resize_identifiers_pagerank(
+/// Position of node `index` in `layout`. Flat layouts lie on the XY plane.
+fn layout_position(layout: &Layout<f64>, index: usize) -> Vec3 {
+    let pos = layout.points.get(index);
+    Vec3::new(
+        pos[0] as f32,
+        pos[1] as f32,
+        pos.get(2).map_or(0.0, |&z| z as f32),
+    )
+}
+
+fn move_identifiers_forceatlas2(
     mut commands: Commands,
-    mut ev: EventReader<PageRankEvent>,
+    mut ev: EventReader<Forceatlas2Event>,
+    mut job: Local<Option<Job<(Vec<(Entity, Vec3)>, Vec<Entity>)>>>,
+    mut jobs: ResMut<Jobs>,
+    mut history: ResMut<History>,
     configuration: Res<Configuration>,
-    identifier_query: Query<(Entity, &Transform), With<Identifier>>,
-    conn_query: Query<&Connection, With<Connection>>,
+    isolate_policy: Res<IsolatePolicy>,
+    mut seeded_rng: ResMut<SeededRng>,
+    graph_index: Res<GraphIndex>,
+    identifier_query: Query<(&Transform, Has<Pinned>), With<Identifier>>,
 ) {
-    for settings in ev.read() {
-        // eprintln!("Generating graph...");
+    for e in ev.read() {
+        let Some(mut layout) = build_layout(
+            &graph_index,
+            &e.settings,
+            |entity| {
+                identifier_query
+                    .get(entity)
+                    .ok()
+                    .map(|(transform, _)| (transform.translation, transform.scale.x))
+            },
+            Some(seeded_rng.rng()),
+        ) else {
+            continue;
+        };
 
-        let edges: Vec<(usize, usize)> = conn_query
-            .iter()
-            .map(|connection| {
-                (
-                    connection.from.index() as usize,
-                    connection.to.index() as usize,
-                )
-            })
+        let entities: Vec<Entity> = (0..graph_index.len())
+            .map(|i| graph_index.entity(i))
             .collect();
-
-        // // count the number of unique nodes
-        // let flattened: Vec<usize> = edges
-        //     .clone()
-        //     .into_iter()
-        //     .flat_map(|(a, b)| vec![a, b])
-        //     .collect();
-
-        // let unique_values: HashSet<_> = flattened.into_iter().collect();
-        // unique_values.iter().enumerate().for_each(|(i, v)| {
-        //     println!("{}: {}", i, v);
-        // });
-
-        let (unique_values, indexed_vec) = process_vec(&edges);
-
-        // println!("Unique Values: {:?}", unique_values);
-        // println!("Indexed Vec: {:?}", indexed_vec);
-
-        let graph: DirectedCsrGraph<usize> = GraphBuilder::new().edges(indexed_vec).build();
-
-        let (ranks, _, _) = page_rank(&graph, settings.config);
-        // println!("Ranks: {:?}", ranks);
-        let mut cloned_ranks = ranks.clone();
-        normalize(&mut cloned_ranks);
-
-        // println!("Normalized Ranks: {:?}", cloned_ranks);
-        for (i, rank) in cloned_ranks.iter().enumerate() {
-            if let Ok((entity, transform)) =
-                identifier_query.get(Entity::from_raw(unique_values[i] as u32))
-            {
-                let pos = transform.translation;
-                commands.entity(entity).insert(
-                    transform.ease_to(
-                        Transform::from_xyz(pos.x, pos.y, pos.z)
-                            .with_scale(Vec3::ONE * (*rank * 3.0 + 0.5)),
-                        EaseFunction::QuarticOut,
-                        bevy_easings::EasingType::Once {
-                            duration: (std::time::Duration::from_secs(
-                                configuration.animation_duration,
-                            )),
-                        },
-                    ),
-                );
+        let pinned = pinned_positions(&entities, |entity| {
+            identifier_query
+                .get(entity)
+                .ok()
+                .filter(|(_, pinned)| *pinned)
+                .map(|(transform, _)| transform.translation)
+        });
+        hold_pinned(&mut layout, &pinned);
+        let iterations = e.settings.iterations;
+        // Replacing a running job cancels it.
+        *job = Some(jobs.spawn("ForceAtlas2", move |progress| {
+            for iteration in 0..iterations {
+                if progress.is_cancelled() {
+                    break;
+                }
+                layout.iteration();
+                hold_pinned(&mut layout, &pinned);
+                progress.set((iteration + 1) as f32 / iterations as f32);
             }
-        }
-
-        // for (h1, h2) in layout.edges.iter() {
-        //     if let Ok((entity, transform)) = identifier_query.get(Entity::from_raw(*h1 as u32)) {
-        //         let pos = layout.points.get(*h1);
-        //         commands.entity(entity).insert(transform.ease_to(
-        //             Transform::from_xyz(pos[0], pos[1], pos[2]),
-        //             EaseFunction::QuarticOut,
-        //             bevy_easings::EasingType::Once {
-        //                 duration: (std::time::Duration::from_secs(
-        //                     configuration.animation_duration,
-        //                 )),
-        //             },
-        //         ));
-        //     }
-
-        //     if let Ok((entity, transform)) = identifier_query.get(Entity::from_raw(*h2 as u32)) {
-        //         let pos = layout.points.get(*h2);
-        //         commands.entity(entity).insert(transform.ease_to(
-        //             Transform::from_xyz(pos[0], pos[1], pos[2]),
-        //             EaseFunction::QuarticOut,
-        //             bevy_easings::EasingType::Once {
-        //                 duration: (std::time::Duration::from_secs(
-        //                     configuration.animation_duration,
-        //                 )),
-        //             },
-        //         ));
-        //     }
-        // }
+            // Identifiers without connections have no mass; the isolate policy places them.
+            let (connected, isolates): (Vec<_>, Vec<_>) = layout
+                .masses
+                .iter()
+                .enumerate()
+                .partition(|(_, mass)| **mass != 0.0);
+            (
+                connected
+                    .into_iter()
+                    .map(|(index, _)| (entities[index], layout_position(&layout, index)))
+                    .collect(),
+                isolates
+                    .into_iter()
+                    .map(|(index, _)| entities[index])
+                    .collect(),
+            )
+        }));
     }
-}
-
-fn normalize(vec: &mut [f32]) {
-    let min = vec.iter().cloned().fold(f32::INFINITY, f32::min);
-    let max = vec.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
 
-    if max - min != 0.0 {
-        vec.iter_mut().for_each(|x| *x = (*x - min) / (max - min));
+    if let Some((positions, isolates)) = poll(&mut job) {
+        let isolate_positions = isolate_positions(
+            *isolate_policy,
+            positions.iter().map(|(_, position)| *position),
+            isolates.len(),
+        );
+        ease_identifiers_to(
+            &mut commands,
+            &mut history,
+            &configuration,
+            &identifier_query,
+            positions
+                .into_iter()
+                .chain(isolates.into_iter().zip(isolate_positions)),
+        );
     }
 }
 
-fn process_vec(input: &[(usize, usize)]) -> (Vec<usize>, Vec<(usize, usize)>) {
-    // Step 1: Extract and sort unique values
-    let mut unique_values: Vec<usize> = input.iter().flat_map(|(a, b)| vec![*a, *b]).collect();
-    unique_values.sort_unstable();
-    unique_values.dedup();
-
-    // Step 2: Create a map for value to index
-    let value_to_index: std::collections::HashMap<_, _> = unique_values
-        .iter()
-        .enumerate()
-        .map(|(index, &value)| (value, index))
-        .collect();
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LiveLayoutState {
+    #[default]
+    Stopped,
+    Running,
+    Paused,
+}
 
-    // Step 3: Transform the original vector
-    let indexed_vec: Vec<(usize, usize)> = input
-        .iter()
-        .map(|(a, b)| (value_to_index[&a], value_to_index[&b]))
-        .collect();
+/// ForceAtlas2 layout that keeps running, a few iterations per frame.
+/// The layout is rebuilt from the current positions whenever the graph changes.
+#[derive(Resource, Default)]
+pub struct LiveForceAtlas2 {
+    layout: Option<Layout<f64>>,
+    settings: ForceAtlas2Settings,
+    /// The graph changed since `layout` was built.
+    stale: bool,
+    pub state: LiveLayoutState,
+    /// Iterations run since the layout was started.
+    pub iterations: u64,
+    /// Mean distance an identifier moved per iteration in the last frame.
+    /// It falls towards zero as the layout converges.
+    pub displacement: f32,
+    pub iterations_per_second: f32,
+}
 
-    (unique_values, indexed_vec)
+fn build_live_layout(
+    graph_index: &GraphIndex,
+    settings: &ForceAtlas2Settings,
+    identifier_query: &Query<(&mut Transform, Has<Pinned>), With<Identifier>>,
+) -> Option<Layout<f64>> {
+    build_layout(
+        graph_index,
+        settings,
+        |entity| {
+            identifier_query
+                .get(entity)
+                .ok()
+                .map(|(transform, _)| (transform.translation, transform.scale.x))
+        },
+        None,
+    )
 }
 
-pub struct PageRankIterations(usize);
-impl Default for PageRankIterations {
-    fn default() -> Self {
-        Self(20)
+fn control_forceatlas2_live(
+    mut commands: Commands,
+    mut ev: EventReader<Forceatlas2LiveEvent>,
+    mut live: ResMut<LiveForceAtlas2>,
+    mut history: ResMut<History>,
+    graph_index: Res<GraphIndex>,
+    identifier_query: Query<(&mut Transform, Has<Pinned>), With<Identifier>>,
+) {
+    for e in ev.read() {
+        match e {
+            Forceatlas2LiveEvent::Start { settings } => {
+                // A run until the next pause is undone as a whole.
+                if live.state != LiveLayoutState::Running {
+                    history.record(Edit::Transforms(
+                        (0..graph_index.len())
+                            .filter_map(|i| {
+                                let entity = graph_index.entity(i);
+                                let (transform, _) = identifier_query.get(entity).ok()?;
+                                Some((entity, *transform))
+                            })
+                            .collect(),
+                    ));
+                }
+                live.settings = settings.clone();
+                // Switching between 2D and 3D or toggling overlap prevention needs a new layout.
+                let rebuild = !live.layout.as_ref().is_some_and(|layout| {
+                    layout.get_settings().dimensions == settings.dimensions
+                        && layout.sizes.is_some() == settings.prevent_overlapping.is_some()
+                });
+                if rebuild {
+                    live.layout = build_live_layout(&graph_index, settings, &identifier_query);
+                    live.iterations = 0;
+                } else if let Some(layout) = live.layout.as_mut() {
+                    layout.set_settings(settings.to_settings());
+                }
+                live.state = LiveLayoutState::Running;
+                // Running easings would fight the live layout for the transforms.
+                for i in 0..graph_index.len() {
+                    commands
+                        .entity(graph_index.entity(i))
+                        .remove::<(EasingComponent<Transform>, EasingChainComponent<Transform>)>();
+                }
+            }
+            Forceatlas2LiveEvent::Pause => {
+                if live.state == LiveLayoutState::Running {
+                    live.state = LiveLayoutState::Paused;
+                }
+            }
+            Forceatlas2LiveEvent::Stop => {
+                live.layout = None;
+                live.state = LiveLayoutState::Stopped;
+                live.displacement = 0.0;
+                live.iterations_per_second = 0.0;
+            }
+        }
     }
 }
-pub struct PageRankTolerance(f64);
-impl Default for PageRankTolerance {
-    fn default() -> Self {
-        Self(1.0E-4f64)
+
+fn run_forceatlas2_live(
+    time: Res<Time>,
+    mut live: ResMut<LiveForceAtlas2>,
+    graph_index: Res<GraphIndex>,
+    mut identifier_query: Query<(&mut Transform, Has<Pinned>), With<Identifier>>,
+    easing_query: Query<(), With<EasingComponent<Transform>>>,
+) {
+    if graph_index.is_changed() {
+        live.stale = true;
+    }
+    if live.state != LiveLayoutState::Running {
+        return;
+    }
+    if live.stale || live.layout.is_none() {
+        live.stale = false;
+        live.layout = build_live_layout(&graph_index, &live.settings, &identifier_query);
     }
-}
 
-pub struct PageRankDamping(f32);
-impl Default for PageRankDamping {
-    fn default() -> Self {
-        Self(0.8500f32)
+    let iterations_per_frame = live.settings.iterations_per_frame;
+    let Some(layout) = live.layout.as_mut() else {
+        return;
+    };
+    // Pinned identifiers may have been dragged since the last frame.
+    let entities: Vec<Entity> = (0..layout.masses.len())
+        .map(|i| graph_index.entity(i))
+        .collect();
+    let pinned = pinned_positions(&entities, |entity| {
+        identifier_query
+            .get(entity)
+            .ok()
+            .filter(|(_, pinned)| *pinned)
+            .map(|(transform, _)| transform.translation)
+    });
+    hold_pinned(layout, &pinned);
+    let before: Vec<Vec3> = (0..layout.masses.len())
+        .map(|index| layout_position(layout, index))
+        .collect();
+    for _ in 0..iterations_per_frame {
+        layout.iteration();
+        hold_pinned(layout, &pinned);
     }
-}
 
-pub fn page_rank_ui(
-    query: Query<&mut EguiContext, With<PrimaryWindow>>,
-    mut iterations: Local<PageRankIterations>,
-    mut tolerance: Local<PageRankTolerance>,
-    mut damping: Local<PageRankDamping>,
-    mut ev: EventWriter<PageRankEvent>,
-) {
-    let mut egui_context = query.single().clone();
+    let mut moved = 0.0;
+    let mut moving = 0;
+    for (index, mass) in layout.masses.iter().enumerate() {
+        if *mass == 0.0 {
+            continue;
+        }
+        let entity = graph_index.entity(index);
+        let position = layout_position(layout, index);
+        moved += position.distance(before[index]);
+        moving += 1;
+        // Identifiers that are still easing in are placed once they are done.
+        if easing_query.contains(entity) {
+            continue;
+        }
+        if let Ok((mut transform, false)) = identifier_query.get_mut(entity) {
+            transform.translation = position;
+        }
+    }
 
-    egui::Window::new("Page Rank")
-        .vscroll(false)
-        .hscroll(false)
-        .default_width(250.0)
-        .resizable(false)
-        .show(egui_context.get_mut(), |ui| {
-            ui.add(egui::Slider::new(&mut iterations.0, 1..=100).text("Iterations"));
-            ui.add(egui::Slider::new(&mut damping.0, 0.0..=2.0).text("Damping"));
-            ui.add(egui::Slider::new(&mut tolerance.0, 0.0..=0.001).text("Tolerance"));
-            if ui.button("Resize").clicked() {
-                ev.send(PageRankEvent {
-                    config: PageRankConfig {
-                        max_iterations: iterations.0,
-                        tolerance: tolerance.0,
-                        damping_factor: damping.0,
-                    },
-                });
-            }
-        });
+    live.iterations += iterations_per_frame as u64;
+    live.displacement = if moving > 0 {
+        moved / (moving as f32 * iterations_per_frame.max(1) as f32)
+    } else {
+        0.0
+    };
+    if time.delta_seconds() > 0.0 {
+        live.iterations_per_second = iterations_per_frame as f32 / time.delta_seconds();
+    }
 }
@@ -5,12 +5,26 @@ use bevy_panorbit_camera::PanOrbitCamera;
 use bevy_window::PrimaryWindow;
 
 use crate::{
+    communities::communities_ui,
+    connectivity::components_ui,
+    edit::EditMode,
     events::{
-        DeselectIdentifierEvent, MoveIdentifiersRndEvent, SelectRandomConnectedIdentifierEvent,
-        SelectRandomIdentifierEvent,
+        DeselectIdentifierEvent, ExportGraphEvent, ImportGraphEvent, MoveIdentifiersRndEvent,
+        RedoEvent, SelectIdentifierEvent, SelectRandomConnectedIdentifierEvent,
+        SelectRandomIdentifierEvent, UndoEvent,
     },
-    resources::Configuration,
-    simulation::{force_atlas_ui, page_rank_ui, simulation_ui},
+    formats::{ExportStatus, GraphFormat, ImportStatus},
+    history::History,
+    inspector::inspector_ui,
+    jobs::Jobs,
+    layout::layout_ui,
+    metrics::metrics_ui,
+    paths::path_ui,
+    resources::{Configuration, SeededRng},
+    search::{search_ui, Search},
+    selection::selection_ui,
+    session::session_ui,
+    simulation::{force_atlas_ui, simulation_ui},
     util::{calculate_from_translation_and_focus, random_point_in_sphere},
 };
 
@@ -20,8 +34,17 @@ pub struct UiState {
     pub show_config: bool,
     pub show_actions: bool,
     pub show_forceatlas: bool,
+    pub show_layout: bool,
     pub show_simulation: bool,
-    pub show_pagerank: bool,
+    pub show_metrics: bool,
+    pub show_communities: bool,
+    pub show_components: bool,
+    pub show_path: bool,
+    pub show_selection: bool,
+    pub show_inspector: bool,
+    pub show_import: bool,
+    pub show_session: bool,
+    pub show_export: bool,
 }
 
 pub struct UiPlugin;
@@ -37,17 +60,46 @@ impl Plugin for UiPlugin {
                     configuration_ui.run_if(|state: Res<UiState>| state.show_config),
                     actions_ui.run_if(|state: Res<UiState>| state.show_actions),
                     force_atlas_ui.run_if(|state: Res<UiState>| state.show_forceatlas),
+                    layout_ui.run_if(|state: Res<UiState>| state.show_layout),
                     simulation_ui.run_if(|state: Res<UiState>| state.show_simulation),
-                    page_rank_ui.run_if(|state: Res<UiState>| state.show_pagerank),
+                    metrics_ui.run_if(|state: Res<UiState>| state.show_metrics),
+                    communities_ui.run_if(|state: Res<UiState>| state.show_communities),
+                    components_ui.run_if(|state: Res<UiState>| state.show_components),
+                    path_ui.run_if(|state: Res<UiState>| state.show_path),
+                    selection_ui.run_if(|state: Res<UiState>| state.show_selection),
+                    inspector_ui.run_if(|state: Res<UiState>| state.show_inspector),
+                    search_ui.run_if(|search: Res<Search>| !search.query.trim().is_empty()),
+                    import_ui.run_if(|state: Res<UiState>| state.show_import),
+                    export_ui.run_if(|state: Res<UiState>| state.show_export),
+                    session_ui.run_if(|state: Res<UiState>| state.show_session),
                 ),
             );
     }
 }
 
-fn ui(query: Query<&mut EguiContext, With<PrimaryWindow>>, mut state: ResMut<UiState>) {
+fn ui(
+    query: Query<&mut EguiContext, With<PrimaryWindow>>,
+    mut state: ResMut<UiState>,
+    mut search: ResMut<Search>,
+    mut edit_mode: ResMut<EditMode>,
+    history: Res<History>,
+    jobs: Res<Jobs>,
+    mut ev_select: EventWriter<SelectIdentifierEvent>,
+    mut ev_undo: EventWriter<UndoEvent>,
+    mut ev_redo: EventWriter<RedoEvent>,
+) {
     let mut egui_context = query.single().clone();
     egui::TopBottomPanel::top("Top").show(egui_context.get_mut(), |ui| {
         ui.horizontal(|ui| {
+            if ui.button("Import").clicked() {
+                state.show_import = !state.show_import;
+            };
+            if ui.button("Export").clicked() {
+                state.show_export = !state.show_export;
+            };
+            if ui.button("Session").clicked() {
+                state.show_session = !state.show_session;
+            };
             if ui.button("Config").clicked() {
                 state.show_config = !state.show_config;
             };
@@ -57,12 +109,81 @@ fn ui(query: Query<&mut EguiContext, With<PrimaryWindow>>, mut state: ResMut<UiS
             if ui.button("Atlas").clicked() {
                 state.show_forceatlas = !state.show_forceatlas;
             };
-            if ui.button("PageRank").clicked() {
-                state.show_pagerank = !state.show_pagerank;
+            if ui.button("Layout").clicked() {
+                state.show_layout = !state.show_layout;
+            };
+            if ui.button("Metrics").clicked() {
+                state.show_metrics = !state.show_metrics;
+            };
+            if ui.button("Communities").clicked() {
+                state.show_communities = !state.show_communities;
+            };
+            if ui.button("Components").clicked() {
+                state.show_components = !state.show_components;
+            };
+            if ui.button("Path").clicked() {
+                state.show_path = !state.show_path;
+            };
+            if ui.button("Selection").clicked() {
+                state.show_selection = !state.show_selection;
+            };
+            if ui.button("Inspector").clicked() {
+                state.show_inspector = !state.show_inspector;
             };
             if ui.button("Simulate").clicked() {
                 state.show_simulation = !state.show_simulation;
             };
+
+            ui.separator();
+            let mut editing = edit_mode.enabled;
+            if ui.toggle_value(&mut editing, "Edit").changed() {
+                edit_mode.enabled = editing;
+            }
+            let undo = ui.add_enabled(history.next_undo().is_some(), egui::Button::new("Undo"));
+            if let Some(edit) = history.next_undo() {
+                undo.clone()
+                    .on_hover_text(format!("Ctrl+Z: {}", edit.describe()));
+            }
+            if undo.clicked() {
+                ev_undo.send(UndoEvent);
+            }
+            let redo = ui.add_enabled(history.next_redo().is_some(), egui::Button::new("Redo"));
+            if let Some(edit) = history.next_redo() {
+                redo.clone()
+                    .on_hover_text(format!("Ctrl+Shift+Z: {}", edit.describe()));
+            }
+            if redo.clicked() {
+                ev_redo.send(RedoEvent);
+            }
+
+            // Edit a copy, so the results are only searched again on changes.
+            let mut query = search.query.clone();
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut query)
+                    .hint_text("Search")
+                    .desired_width(150.0),
+            );
+            if response.changed() {
+                search.query = query;
+            }
+            if response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter)) {
+                if let Some(&entity) = search.results.first() {
+                    ev_select.send(SelectIdentifierEvent(entity));
+                }
+            }
+
+            for job in jobs.running() {
+                ui.separator();
+                ui.add(
+                    egui::ProgressBar::new(job.fraction())
+                        .desired_width(150.0)
+                        .text(job.name())
+                        .animate(true),
+                );
+                if ui.small_button("Cancel").clicked() {
+                    job.cancel();
+                }
+            }
         });
     });
 
@@ -77,6 +198,7 @@ fn ui(query: Query<&mut EguiContext, With<PrimaryWindow>>, mut state: ResMut<UiS
 
 pub fn configuration_ui(
     mut configuration: ResMut<Configuration>,
+    mut seeded_rng: ResMut<SeededRng>,
     query: Query<&mut EguiContext, With<PrimaryWindow>>,
 ) {
     let mut egui_context = query.single().clone();
@@ -92,11 +214,22 @@ pub fn configuration_ui(
                 egui::Slider::new(&mut configuration.animation_duration, 1..=10)
                     .text("Duration (sec)"),
             );
+            ui.horizontal(|ui| {
+                ui.label("Seed");
+                let changed = ui
+                    .add(egui::DragValue::new(&mut configuration.seed))
+                    .changed();
+                // The same seed followed by the same actions repeats a run.
+                if changed || ui.button("Restart").clicked() {
+                    seeded_rng.reseed(configuration.seed);
+                }
+            });
         });
 }
 
 pub fn actions_ui(
     configuration: ResMut<Configuration>,
+    mut seeded_rng: ResMut<SeededRng>,
     query: Query<&mut EguiContext, With<PrimaryWindow>>,
     mut camera_q: Query<&mut PanOrbitCamera, With<PanOrbitCamera>>,
     mut ev_rnd_id: EventWriter<SelectRandomIdentifierEvent>,
@@ -128,7 +261,8 @@ pub fn actions_ui(
             ui.separator();
             if ui.button("Move camera randomly").clicked() {
                 if let Ok(mut camera) = camera_q.get_single_mut() {
-                    let (x, y, z) = random_point_in_sphere(configuration.container_size);
+                    let (x, y, z) =
+                        random_point_in_sphere(seeded_rng.rng(), configuration.container_size);
                     let (alpha, beta, radius) =
                         calculate_from_translation_and_focus(Vec3::new(x, y, z), Vec3::ZERO);
                     camera.target_alpha = alpha;
@@ -151,3 +285,136 @@ pub fn actions_ui(
             }
         });
 }
+
+pub struct ImportForm {
+    path: String,
+    format: Option<GraphFormat>,
+    replace: bool,
+}
+impl Default for ImportForm {
+    fn default() -> Self {
+        Self {
+            path: String::new(),
+            format: None,
+            replace: true,
+        }
+    }
+}
+
+pub fn import_ui(
+    query: Query<&mut EguiContext, With<PrimaryWindow>>,
+    mut form: Local<ImportForm>,
+    status: Res<ImportStatus>,
+    mut ev: EventWriter<ImportGraphEvent>,
+) {
+    let mut egui_context = query.single().clone();
+
+    egui::Window::new("Import")
+        .vscroll(false)
+        .hscroll(false)
+        .default_width(250.0)
+        .resizable(false)
+        .show(egui_context.get_mut(), |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Path");
+                ui.text_edit_singleline(&mut form.path);
+            });
+            egui::ComboBox::from_label("Format")
+                .selected_text(form.format.map_or("From extension", |format| format.name()))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut form.format, None, "From extension");
+                    for format in GraphFormat::ALL {
+                        ui.selectable_value(&mut form.format, Some(format), format.name());
+                    }
+                });
+            ui.checkbox(&mut form.replace, "Replace current graph");
+            if ui.button("Import").clicked() && !form.path.trim().is_empty() {
+                ev.send(ImportGraphEvent {
+                    path: form.path.trim().into(),
+                    format: form.format,
+                    replace: form.replace,
+                });
+            }
+
+            if let Some(message) = &status.message {
+                ui.separator();
+                ui.label(message);
+            }
+            if !status.warnings.is_empty() {
+                ui.collapsing(format!("{} skipped rows", status.warnings.len()), |ui| {
+                    egui::ScrollArea::vertical()
+                        .max_height(200.0)
+                        .show(ui, |ui| {
+                            for warning in status.warnings.iter() {
+                                ui.label(warning);
+                            }
+                        });
+                });
+            }
+        });
+}
+
+pub struct ExportForm {
+    path: String,
+    format: GraphFormat,
+    selected_only: bool,
+}
+impl Default for ExportForm {
+    fn default() -> Self {
+        Self {
+            path: "graph.graphml".to_string(),
+            format: GraphFormat::Graphml,
+            selected_only: false,
+        }
+    }
+}
+
+pub fn export_ui(
+    query: Query<&mut EguiContext, With<PrimaryWindow>>,
+    mut form: Local<ExportForm>,
+    status: Res<ExportStatus>,
+    mut ev: EventWriter<ExportGraphEvent>,
+) {
+    let mut egui_context = query.single().clone();
+
+    egui::Window::new("Export")
+        .vscroll(false)
+        .hscroll(false)
+        .default_width(250.0)
+        .resizable(false)
+        .show(egui_context.get_mut(), |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Path");
+                if ui.text_edit_singleline(&mut form.path).changed() {
+                    if let Some(format) = GraphFormat::from_path(form.path.trim().as_ref())
+                        .filter(GraphFormat::is_writable)
+                    {
+                        form.format = format;
+                    }
+                }
+            });
+            egui::ComboBox::from_label("Format")
+                .selected_text(form.format.name())
+                .show_ui(ui, |ui| {
+                    for format in GraphFormat::ALL
+                        .into_iter()
+                        .filter(GraphFormat::is_writable)
+                    {
+                        ui.selectable_value(&mut form.format, format, format.name());
+                    }
+                });
+            ui.checkbox(&mut form.selected_only, "Selected identifiers only");
+            if ui.button("Export").clicked() && !form.path.trim().is_empty() {
+                ev.send(ExportGraphEvent {
+                    path: form.path.trim().into(),
+                    format: form.format,
+                    selected_only: form.selected_only,
+                });
+            }
+
+            if let Some(message) = &status.message {
+                ui.separator();
+                ui.label(message);
+            }
+        });
+}
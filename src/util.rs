@@ -17,8 +17,7 @@ pub fn calculate_from_translation_and_focus(translation: Vec3, focus: Vec3) -> (
     (alpha, beta, radius)
 }
 
-pub fn random_point_in_sphere(radius: f32) -> (f32, f32, f32) {
-    let mut rng = rand::thread_rng();
+pub fn random_point_in_sphere(rng: &mut impl Rng, radius: f32) -> (f32, f32, f32) {
     let theta = rng.gen::<f32>() * 2.0 * PI as f32;
     let phi = rng.gen::<f32>() * PI as f32;
     let u = rng.gen::<f32>() * radius.powi(3);